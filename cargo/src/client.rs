@@ -1,30 +1,90 @@
-use rand::Rng;
-use std::io::{self, Read, Write};
+pub mod clock;
+pub mod simulation;
+
+use clock::{Clock, ManualClock, SystemClock};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io::{self, BufRead, BufReader, Write};
 use std::net::TcpStream;
-use std::thread::sleep;
 use std::time::Duration;
 
-fn should_reserve() -> bool {
-    let mut rng = rand::thread_rng();
+// Comandos cuya respuesta tiene varias líneas y termina con la línea "END"
+const MULTILINE_COMMANDS: [&str; 4] = ["map", "stats", "availability", "health"];
+
+pub struct Connection {
+    /**
+    Conexión con el servidor, una solicitud y una respuesta por línea.
+    El lector guarda lo que llegó de más entre una respuesta y la siguiente.
+    */
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Connection { writer: stream, reader })
+    }
+
+    pub fn send(&mut self, request: &str) -> io::Result<String> {
+        /**
+        Envía una solicitud terminada en salto de línea y lee la respuesta.
+        Las respuestas de varias líneas (mapa, estadísticas, salud y `find` sin asientos)
+        se leen hasta la línea "END", que no se incluye.
+
+        Retorna: La respuesta, cada línea con su salto de línea
+        */
+        self.writer.write_all(format!("{}\n", request.trim_end()).as_bytes())?;
+
+        let mut response = self.read_line()?;
+        let command = request.split_whitespace().next().unwrap_or("");
+        let multiline = (MULTILINE_COMMANDS.contains(&command) && !response.starts_with("ERR "))
+            || response.starts_with("ERR NO_SEATS_FOUND");
+        if multiline {
+            loop {
+                let line = self.read_line()?;
+                if line.trim_end() == "END" {
+                    break;
+                }
+                response.push_str(&line);
+            }
+        }
+        Ok(response)
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        // Una línea de la respuesta, el cierre de la conexión es un error
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Server closed the connection"));
+        }
+        Ok(line)
+    }
+}
+
+fn should_reserve(rng: &mut StdRng) -> bool {
     rng.gen_bool(0.5)  // Genera un booleano con 50% de probabilidad
 }
 
 
-fn handle_request(stream: &mut TcpStream, request: &str) -> io::Result<String> {
+fn handle_request(
+    connection: &mut Connection,
+    request: &str,
+    rng: &mut StdRng,
+    clock: &dyn Clock,
+) -> io::Result<String> {
     /**
     Maneja la solicitud enviada al servidor yb procesa la respuesta
     Parametros:
-        stream= Conexiona ocn el servidor
+        connection= Conexiona ocn el servidor
         request= Solicitud a enviar al servidor
+        rng= Generador con el que se decide si reservar
+        clock= Reloj con el que se simulan los retrasos
 
     Retorna: La respuesta del servidor
     */
     println!("Sending request: {}", request);
-    stream.write_all(request.as_bytes())?;
-
-    let mut buffer = [0; 512];
-    let size = stream.read(&mut buffer)?;
-    let response = String::from_utf8_lossy(&buffer[..size]).to_string();
+    let response = connection.send(request)?;
     println!("Server response: {}", response);
 
     // Simula la aceptación automática de las reservas encontradas
@@ -32,11 +92,11 @@ fn handle_request(stream: &mut TcpStream, request: &str) -> io::Result<String> {
         if response.contains("No suitable seats found.") {
             println!("No seats found. Skipping reservation.");
         } else {
-            if should_reserve() {
+            if should_reserve(rng) {
                 println!("Seats found. Automatically reserving...");
 
                 // Simula un retraso antes de realizar la reserva
-                sleep(Duration::from_secs(2));
+                clock.sleep(Duration::from_secs(2));
 
                 // Obtener el tipo de categoría de la respuesta
                 let category_parent = response.split_whitespace().last().unwrap_or("");
@@ -68,12 +128,8 @@ fn handle_request(stream: &mut TcpStream, request: &str) -> io::Result<String> {
                 let mut response_server: Vec<String> = Vec::new();
                 for reserve in reserve_requests {
                     println!("Sending reserve request: {}", reserve);
-                    stream.write_all(reserve.as_bytes())?;
-
                     // Lee la respuesta de la reserva
-                    let mut reserve_buffer = [0; 512];
-                    let reserve_size = stream.read(&mut reserve_buffer)?;
-                    let reserve_response = String::from_utf8_lossy(&reserve_buffer[..reserve_size]).to_string();
+                    let reserve_response = connection.send(&reserve)?;
                     response_server.push(reserve_response.clone()); // Clona la respuesta antes de moverla
                     println!("Server response to reservation: {}", reserve_response);
                 }
@@ -81,11 +137,7 @@ fn handle_request(stream: &mut TcpStream, request: &str) -> io::Result<String> {
                 // Mandar al servidor `response_server`
                 for response in response_server {
                     println!("Lista Reservada a comprar: {:?}", response);
-                    stream.write_all(response.as_bytes())?;
-
-                    let mut reserve_buffer = [0; 512];
-                    let reserve_size = stream.read(&mut reserve_buffer)?;
-                    let reserve_response = String::from_utf8_lossy(&reserve_buffer[..reserve_size]).to_string();
+                    let reserve_response = connection.send(&response)?;
                     println!("Server response to reservation: {}", reserve_response);
                 }
             } else {
@@ -101,9 +153,23 @@ pub fn run_client() {
     /**
     Simula al cliente con datos quemados, que intenta conectarse a un servidor en la direccion establecida
     */
+    let mut rng = StdRng::from_entropy();
+    run_client_with(&mut rng, &SystemClock::new());
+}
+
+pub fn run_seeded_client(seed: u64) {
+    /**
+    Simula al cliente con una semilla fija y un reloj manual,
+    las decisiones de reserva se repiten igual en cada ejecución
+    */
+    let mut rng = StdRng::seed_from_u64(seed);
+    run_client_with(&mut rng, &ManualClock::new());
+}
+
+fn run_client_with(rng: &mut StdRng, clock: &dyn Clock) {
     loop {
-        match TcpStream::connect("127.0.0.1:7878") {
-            Ok(mut stream) => {
+        match TcpStream::connect("127.0.0.1:7878").and_then(Connection::new) {
+            Ok(mut connection) => {
                 // Datos quemados para simular múltiples solicitudes
                 let requests = vec![
                     "find VIP 4",    // Solicita 4 asientos en la categoría VIP
@@ -115,11 +181,11 @@ pub fn run_client() {
                 ];
 
                 for request in requests {
-                    if let Err(e) = handle_request(&mut stream, request) {
+                    if let Err(e) = handle_request(&mut connection, request, rng, clock) {
                         eprintln!("Error handling request: {}", e);
                     }
                     // Agrega un retraso para simular la concurrencia
-                    sleep(Duration::from_secs(2));
+                    clock.sleep(Duration::from_secs(2));
                 }
 
                 println!("Client finished requests. Exiting...");
@@ -128,8 +194,106 @@ pub fn run_client() {
             Err(e) => {
                 eprintln!("Failed to connect to server: {}", e);
                 println!("Retrying in 5 seconds...");
-                sleep(Duration::from_secs(5));
+                // Espera real aun con el reloj manual, que no bloquea y reintentaría sin pausa
+                std::thread::sleep(Duration::from_secs(5));
             }
         }
     }
 }
+
+pub fn show_seat_map() {
+    /**
+    Pide al servidor el mapa de asientos y lo muestra en la terminal.
+    El mapa tiene varias líneas, `Connection::send` lee hasta la línea "END".
+    */
+    let mut connection = match TcpStream::connect("127.0.0.1:7878").and_then(Connection::new) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("Failed to connect to server: {}", e);
+            return;
+        }
+    };
+    match connection.send("map") {
+        Ok(map) => print!("{}", map),
+        Err(e) => eprintln!("Failed to read seat map: {}", e),
+    }
+}
+
+pub fn watch_seat_map(filters: &[String]) {
+    /**
+    Se suscribe a los cambios de asientos y vuelve a dibujar el mapa cada vez
+    que cambia un asiento. Los filtros son tipos y/o zonas, por ejemplo `VIP ZonaA`.
+    Corre hasta que el servidor cierre la conexión.
+    */
+    // El servidor puede estar arrancando, se reintenta igual que el cliente simulado
+    let stream = loop {
+        match TcpStream::connect("127.0.0.1:7878") {
+            Ok(stream) => break stream,
+            Err(e) => {
+                eprintln!("Failed to connect to server: {}", e);
+                println!("Retrying in 1 second...");
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+    };
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Failed to open connection: {}", e);
+            return;
+        }
+    };
+    let subscribe = format!("subscribe {}", filters.join(" "));
+    if let Err(e) = writer.write_all(format!("{}\n", subscribe.trim_end()).as_bytes()) {
+        eprintln!("Failed to subscribe: {}", e);
+        return;
+    }
+
+    let mut lines = BufReader::new(stream).lines();
+    match lines.next() {
+        Some(Ok(line)) if line.starts_with("Subscribed") => println!("{}", line),
+        Some(Ok(line)) => {
+            eprintln!("{}", line);
+            return;
+        }
+        _ => return,
+    }
+
+    // Cada cambio de asiento pide el mapa de nuevo, se dibuja desde arriba de la terminal
+    let mut refresh = true;
+    let mut awaiting_map = false;
+    let mut events: Vec<String> = Vec::new();
+    loop {
+        // Un mapa a la vez, los cambios que llegan mientras tanto se dibujan en el siguiente
+        if refresh && !awaiting_map {
+            print!("\x1b[2J\x1b[H");
+            if let Err(e) = writer.write_all(b"map\n") {
+                eprintln!("Failed to request seat map: {}", e);
+                return;
+            }
+            refresh = false;
+            awaiting_map = true;
+        }
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(e)) => {
+                eprintln!("Failed to read from server: {}", e);
+                return;
+            }
+            None => {
+                println!("Server closed the connection.");
+                return;
+            }
+        };
+        if line.starts_with("EVENT") {
+            // Los eventos se muestran debajo del mapa que se dibuja después
+            refresh |= line.starts_with("EVENT seat");
+            events.push(line);
+        } else if line == "END" {
+            awaiting_map = false;
+            events.drain(..).for_each(|event| println!("{}", event));
+        } else {
+            println!("{}", line);
+        }
+    }
+}
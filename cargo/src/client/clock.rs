@@ -0,0 +1,67 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub trait Clock: Send + Sync {
+    /**
+    Reloj que usan los clientes simulados para medir el tiempo y esperar.
+    Permite reemplazar el reloj real por uno manual y repetir una simulación.
+    */
+    fn now(&self) -> Duration;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock {
+    /**
+    Reloj real, `now` es el tiempo transcurrido desde su creación
+    */
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+pub struct ManualClock {
+    /**
+    Reloj virtual, `sleep` avanza el tiempo sin bloquear el hilo
+    */
+    now: Mutex<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock { now: Mutex::new(Duration::ZERO) }
+    }
+
+    pub fn advance_to(&self, time: Duration) {
+        // El tiempo nunca retrocede
+        let mut now = self.now.lock().unwrap();
+        if time > *now {
+            *now = time;
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+}
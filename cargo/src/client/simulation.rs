@@ -0,0 +1,670 @@
+use super::clock::{Clock, ManualClock, SystemClock};
+use super::Connection;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Tiempo máximo de espera por una respuesta, un servidor bloqueado cuenta como error
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+pub struct RequestMix {
+    /**
+    Representa un tipo de solicitud que puede hacer un comprador simulado:
+    - `typ`: Tipo de asiento ("VIP" o "General").
+    - `seat_count`: Cantidad de asientos a buscar.
+    - `weight`: Peso relativo con el que se elige esta solicitud.
+    */
+    pub typ: String,
+    pub seat_count: u32,
+    pub weight: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct LoadConfig {
+    /**
+    Configuración de la prueba de carga:
+    - `address`: Dirección del servidor.
+    - `buyers`: Cantidad de compradores concurrentes.
+    - `requests_per_buyer`: Búsquedas que hace cada comprador.
+    - `mix`: Mezcla de solicitudes a generar.
+    - `think_time_min` / `think_time_max`: Rango de espera entre solicitudes.
+    - `reserve_probability`: Probabilidad de reservar los asientos encontrados.
+    - `seed`: Semilla base, cada comprador usa `seed + id`.
+    - `deterministic`: Intercala a los compradores en un solo hilo con un reloj manual.
+    */
+    pub address: String,
+    pub buyers: usize,
+    pub requests_per_buyer: usize,
+    pub mix: Vec<RequestMix>,
+    pub think_time_min: Duration,
+    pub think_time_max: Duration,
+    pub reserve_probability: f64,
+    pub seed: u64,
+    pub deterministic: bool,
+}
+
+impl LoadConfig {
+    // Configuración por defecto, basada en las solicitudes quemadas de `run_client`
+    pub fn new() -> Self {
+        LoadConfig {
+            address: "127.0.0.1:7878".to_string(),
+            buyers: 10,
+            requests_per_buyer: 6,
+            mix: vec![
+                RequestMix { typ: "VIP".to_string(), seat_count: 4, weight: 1 },
+                RequestMix { typ: "General".to_string(), seat_count: 2, weight: 2 },
+                RequestMix { typ: "VIP".to_string(), seat_count: 1, weight: 1 },
+                RequestMix { typ: "VIP".to_string(), seat_count: 5, weight: 1 },
+                RequestMix { typ: "General".to_string(), seat_count: 3, weight: 1 },
+            ],
+            think_time_min: Duration::from_millis(0),
+            think_time_max: Duration::from_millis(200),
+            reserve_probability: 0.5,
+            seed: 0,
+            deterministic: false,
+        }
+    }
+
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        /**
+        Construye la configuración a partir de los argumentos de la línea de comandos.
+
+        Opciones:
+        - `--addr <host:puerto>`
+        - `--buyers <n>`
+        - `--requests <n>`
+        - `--mix <tipo:cantidad:peso,...>` por ejemplo `VIP:4:1,General:2:3`
+        - `--think <min_ms>-<max_ms>`
+        - `--reserve <probabilidad>`
+        - `--seed <n>`
+        - `--deterministic`
+        */
+        let mut config = LoadConfig::new();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            if flag == "--deterministic" {
+                config.deterministic = true;
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {}", flag))?;
+            match flag.as_str() {
+                "--addr" => config.address = value.clone(),
+                "--buyers" => config.buyers = parse_number(flag, value)?,
+                "--requests" => config.requests_per_buyer = parse_number(flag, value)?,
+                "--mix" => config.mix = parse_mix(value)?,
+                "--think" => {
+                    let (min, max) = value
+                        .split_once('-')
+                        .ok_or_else(|| format!("Invalid think time: {}", value))?;
+                    config.think_time_min = Duration::from_millis(parse_number(flag, min)?);
+                    config.think_time_max = Duration::from_millis(parse_number(flag, max)?);
+                }
+                "--reserve" => config.reserve_probability = parse_number(flag, value)?,
+                "--seed" => config.seed = parse_number(flag, value)?,
+                _ => return Err(format!("Unknown option: {}", flag)),
+            }
+        }
+
+        if config.mix.iter().all(|mix| mix.weight == 0) {
+            return Err("Request mix needs at least one entry with weight > 0".to_string());
+        }
+        if config.think_time_min > config.think_time_max {
+            return Err("Think time minimum is greater than maximum".to_string());
+        }
+        if !(0.0..=1.0).contains(&config.reserve_probability) {
+            return Err("Reserve probability must be between 0 and 1".to_string());
+        }
+        Ok(config)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+fn parse_mix(value: &str) -> Result<Vec<RequestMix>, String> {
+    // Formato: tipo:cantidad:peso separados por comas
+    value
+        .split(',')
+        .map(|entry| {
+            let parts: Vec<&str> = entry.split(':').collect();
+            match parts.as_slice() {
+                [typ, seat_count, weight] => Ok(RequestMix {
+                    typ: typ.to_string(),
+                    seat_count: parse_number("--mix", seat_count)?,
+                    weight: parse_number("--mix", weight)?,
+                }),
+                _ => Err(format!("Invalid mix entry: {}", entry)),
+            }
+        })
+        .collect()
+}
+
+#[derive(Default, Debug)]
+struct BuyerReport {
+    /**
+    Resultados de un comprador simulado:
+    - `latencies`: Latencias por comando ("find", "reserve", "purchase").
+    - `errors`: Errores de conexión o respuestas vacías.
+    - `not_found`: Búsquedas sin asientos disponibles.
+    - `conflicts`: Reservas o compras rechazadas por el servidor.
+    - `reserved` / `purchased`: Asientos (tipo, zona, número) obtenidos.
+    */
+    latencies: HashMap<&'static str, Vec<Duration>>,
+    errors: usize,
+    not_found: usize,
+    conflicts: usize,
+    reserved: Vec<(String, String, u32)>,
+    purchased: Vec<(String, String, u32)>,
+}
+
+#[derive(Debug)]
+pub struct LoadReport {
+    /**
+    Reporte final de la prueba de carga:
+    - `elapsed`: Duración total de la prueba.
+    - `requests`: Total de solicitudes enviadas al servidor.
+    - `latencies`: Latencias ordenadas por comando.
+    - `collisions`: Asientos reservados por más de un comprador.
+    - `oversold`: Asientos comprados por más de un comprador.
+    - `server_sold`: Asientos que el servidor marcó como comprados durante la prueba,
+      según el comando `stats`.
+    */
+    pub elapsed: Duration,
+    pub buyers: usize,
+    pub requests: usize,
+    pub errors: usize,
+    pub not_found: usize,
+    pub conflicts: usize,
+    pub reserved: usize,
+    pub purchased: usize,
+    pub latencies: HashMap<&'static str, Vec<Duration>>,
+    pub collisions: Vec<(String, String, u32)>,
+    pub oversold: Vec<(String, String, u32)>,
+    pub server_sold: Option<u32>,
+}
+
+impl LoadReport {
+    pub fn oversell_ok(&self) -> bool {
+        // Ningún asiento comprado dos veces y, si se pudo consultar, el servidor vendió lo mismo que los compradores
+        let server_mismatch = self.server_sold.is_some_and(|sold| sold as usize != self.purchased);
+        self.oversold.is_empty() && !server_mismatch
+    }
+
+    pub fn throughput(&self) -> f64 {
+        // Solicitudes por segundo
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.requests as f64 / seconds
+    }
+
+    pub fn print(&self) {
+        /**
+        Muestra el reporte en consola
+        */
+        println!("==== Load test report ====");
+        println!("Buyers: {}", self.buyers);
+        println!("Elapsed: {:.2?}", self.elapsed);
+        println!("Requests: {} ({:.1} req/s)", self.requests, self.throughput());
+        println!("Errors: {}", self.errors);
+        println!("Finds without seats: {}", self.not_found);
+        println!("Rejected reserves/purchases: {}", self.conflicts);
+        println!("Seats reserved: {}", self.reserved);
+        println!("Seats purchased: {}", self.purchased);
+
+        let mut commands: Vec<&&str> = self.latencies.keys().collect();
+        commands.sort();
+        for command in commands {
+            let latencies = &self.latencies[*command];
+            println!(
+                "{:<9} n={:<5} p50={:.2?} p90={:.2?} p99={:.2?} max={:.2?}",
+                command,
+                latencies.len(),
+                percentile(latencies, 0.50),
+                percentile(latencies, 0.90),
+                percentile(latencies, 0.99),
+                latencies.last().copied().unwrap_or_default(),
+            );
+        }
+
+        println!("Reservation collisions: {}", self.collisions.len());
+        for (typ, zone, number) in &self.collisions {
+            println!("  {} {} {}", typ, zone, number);
+        }
+        match self.server_sold {
+            Some(sold) if sold as usize == self.purchased => {
+                println!("Server sold during test: {} (matches buyers)", sold)
+            }
+            Some(sold) => println!(
+                "Server sold during test: {} (MISMATCH, buyers completed {} purchases)",
+                sold, self.purchased
+            ),
+            None => println!("Server sold during test: unknown (stats unavailable)"),
+        }
+        if self.oversell_ok() {
+            println!("Oversell check: OK");
+        } else {
+            println!("Oversell check: FAILED ({} seats sold more than once)", self.oversold.len());
+            for (typ, zone, number) in &self.oversold {
+                println!("  {} {} {}", typ, zone, number);
+            }
+        }
+    }
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    // Percentil por el método del rango más cercano, `sorted` debe estar ordenado
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn fetch_server_sold(address: &str) -> Option<u32> {
+    /**
+    Pregunta al servidor cuántos asientos hay comprados con el comando `stats`.
+    Reintenta la conexión como los compradores, el servidor puede estar arrancando.
+    */
+    let stats = connect(address).ok()?.send("stats").ok()?;
+
+    // Línea: TOTAL total=30 free=20 reserved=5 purchased=2 ...
+    stats
+        .lines()
+        .find(|line| line.starts_with("TOTAL "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("purchased="))?
+        .parse()
+        .ok()
+}
+
+fn timed_send(
+    connection: &mut Connection,
+    report: &mut BuyerReport,
+    command: &'static str,
+    request: &str,
+) -> io::Result<String> {
+    // Envía la solicitud y guarda su latencia
+    let start = Instant::now();
+    let response = connection.send(request)?;
+    report.latencies.entry(command).or_default().push(start.elapsed());
+    Ok(response)
+}
+
+fn parse_seats(response: &str) -> Vec<(String, u32)> {
+    /**
+    Convierte la respuesta de `find` en una lista de (zona, número).
+    El último elemento de la respuesta es (tipo, cantidad) y se descarta.
+    */
+    let parts: Vec<&str> = response.split_whitespace().collect();
+    let seats = match parts.split_last() {
+        Some((_, seats)) => seats,
+        None => return vec![],
+    };
+    seats
+        .iter()
+        .filter_map(|seat| {
+            let trimmed_seat = seat.trim_matches(|c| c == '(' || c == ')');
+            let (zone, number) = trimmed_seat.split_once(',')?;
+            Some((zone.trim().to_string(), number.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn pick_request<'a>(rng: &mut StdRng, mix: &'a [RequestMix]) -> &'a RequestMix {
+    // Elige una solicitud de la mezcla según su peso
+    let total: u32 = mix.iter().map(|entry| entry.weight).sum();
+    let mut choice = rng.gen_range(0..total);
+    for entry in mix {
+        if choice < entry.weight {
+            return entry;
+        }
+        choice -= entry.weight;
+    }
+    &mix[mix.len() - 1]
+}
+
+fn connect(address: &str) -> io::Result<Connection> {
+    // Intenta conectarse varias veces por si el servidor aún no está escuchando.
+    // Espera con el tiempo real aun en modo determinista: el reloj manual no bloquea
+    // y los intentos se agotarían antes de que el servidor arranque
+    let mut attempts = 0;
+    loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+                return Connection::new(stream);
+            }
+            Err(e) if attempts >= 10 => return Err(e),
+            Err(_) => {
+                attempts += 1;
+                thread::sleep(Duration::from_millis(200));
+            }
+        }
+    }
+}
+
+struct Buyer {
+    /**
+    Comprador simulado:
+    - `id`: Identificador del comprador.
+    - `rng`: Generador propio, sembrado con `seed + id`.
+    - `connection`: Conexión con el servidor, `None` si falló.
+    - `remaining`: Búsquedas que le faltan por hacer.
+    */
+    id: usize,
+    rng: StdRng,
+    connection: Option<Connection>,
+    remaining: usize,
+    report: BuyerReport,
+}
+
+impl Buyer {
+    fn new(id: usize, config: &LoadConfig) -> Self {
+        let mut report = BuyerReport::default();
+        let connection = match connect(&config.address) {
+            Ok(connection) => Some(connection),
+            Err(e) => {
+                eprintln!("Buyer {}: failed to connect to server: {}", id, e);
+                report.errors += 1;
+                None
+            }
+        };
+        Buyer {
+            id,
+            rng: StdRng::seed_from_u64(config.seed.wrapping_add(id as u64)),
+            connection,
+            remaining: config.requests_per_buyer,
+            report,
+        }
+    }
+
+    fn step(&mut self, config: &LoadConfig) -> Option<Duration> {
+        /**
+        Hace una búsqueda, decide al azar si reservar los asientos encontrados
+        y compra los que logró reservar.
+
+        Retorna el tiempo de espera antes del siguiente paso,
+        o `None` si el comprador ya terminó.
+        */
+        let connection = self.connection.as_mut()?;
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let request = pick_request(&mut self.rng, &config.mix);
+        if let Err(e) = buy(connection, &mut self.report, &mut self.rng, request, config) {
+            eprintln!("Buyer {}: {}", self.id, e);
+            self.report.errors += 1;
+            self.connection = None;
+            return None;
+        }
+
+        // Tiempo de espera del comprador antes de la siguiente solicitud
+        Some(self.rng.gen_range(config.think_time_min..=config.think_time_max))
+    }
+}
+
+fn run_buyer(id: usize, config: &LoadConfig, clock: &dyn Clock) -> BuyerReport {
+    // Ejecuta todos los pasos de un comprador en el hilo actual
+    let mut buyer = Buyer::new(id, config);
+    while let Some(think_time) = buyer.step(config) {
+        clock.sleep(think_time);
+    }
+    buyer.report
+}
+
+fn run_concurrent(config: &LoadConfig) -> Vec<BuyerReport> {
+    // Cada comprador en su propio hilo, con el reloj real
+    let clock = Arc::new(SystemClock::new());
+    let handles: Vec<_> = (0..config.buyers)
+        .map(|id| {
+            let config = config.clone();
+            let clock = Arc::clone(&clock);
+            thread::spawn(move || run_buyer(id, &config, clock.as_ref()))
+        })
+        .collect();
+
+    handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap_or_default())
+        .collect()
+}
+
+fn run_deterministic(config: &LoadConfig) -> Vec<BuyerReport> {
+    /**
+    Ejecuta a todos los compradores en un solo hilo con un reloj manual.
+    En cada paso avanza el comprador cuyo tiempo de espera termina primero
+    (en empate el de menor id), así el orden de las solicitudes solo depende
+    de la semilla y la simulación se puede repetir exactamente.
+    */
+    let clock = ManualClock::new();
+    let mut buyers: Vec<Buyer> = (0..config.buyers)
+        .map(|id| Buyer::new(id, config))
+        .collect();
+
+    let mut queue: BinaryHeap<Reverse<(Duration, usize)>> =
+        (0..buyers.len()).map(|id| Reverse((Duration::ZERO, id))).collect();
+    while let Some(Reverse((wake_at, id))) = queue.pop() {
+        clock.advance_to(wake_at);
+        if let Some(think_time) = buyers[id].step(config) {
+            queue.push(Reverse((clock.now() + think_time, id)));
+        }
+    }
+
+    buyers.into_iter().map(|buyer| buyer.report).collect()
+}
+
+fn buy(
+    connection: &mut Connection,
+    report: &mut BuyerReport,
+    rng: &mut StdRng,
+    request: &RequestMix,
+    config: &LoadConfig,
+) -> io::Result<()> {
+    // Un ciclo de búsqueda, reserva y compra
+    let find = format!("find {} {}", request.typ, request.seat_count);
+    let response = timed_send(connection, report, "find", &find)?;
+    if response.contains("No suitable seats found.") {
+        report.not_found += 1;
+        return Ok(());
+    }
+    if !rng.gen_bool(config.reserve_probability) {
+        return Ok(());
+    }
+
+    for (zone, number) in parse_seats(&response) {
+        let reserve = format!("reserve {} {} {}", request.typ, zone, number);
+        let response = timed_send(connection, report, "reserve", &reserve)?;
+        // El servidor responde con la solicitud de compra del asiento reservado
+        if !response.starts_with("purchase") {
+            report.conflicts += 1;
+            continue;
+        }
+        report.reserved.push((request.typ.clone(), zone.clone(), number));
+
+        let response = timed_send(connection, report, "purchase", &response)?;
+        if response.starts_with("Purchase completed") {
+            report.purchased.push((request.typ.clone(), zone, number));
+        } else {
+            report.conflicts += 1;
+        }
+    }
+    Ok(())
+}
+
+fn repeated(seats: Vec<(String, String, u32)>) -> Vec<(String, String, u32)> {
+    // Asientos que aparecen más de una vez en la lista
+    let mut counts: HashMap<(String, String, u32), usize> = HashMap::new();
+    for seat in seats {
+        *counts.entry(seat).or_default() += 1;
+    }
+    let mut repeated: Vec<(String, String, u32)> = counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(seat, _)| seat)
+        .collect();
+    repeated.sort();
+    repeated
+}
+
+pub fn run_load(config: &LoadConfig) -> LoadReport {
+    /**
+    Ejecuta la prueba de carga con `config.buyers` compradores concurrentes,
+    cada uno en su propio hilo y con su propia conexión al servidor.
+    Con `config.deterministic` los compradores se intercalan en un solo hilo
+    con un reloj manual, para poder repetir la misma simulación.
+
+    Retorna el reporte con throughput, percentiles de latencia,
+    conflictos y la verificación de sobreventa.
+    */
+    println!(
+        "Starting load test: {} buyers, {} requests each, seed {}{}",
+        config.buyers,
+        config.requests_per_buyer,
+        config.seed,
+        if config.deterministic { " (deterministic)" } else { "" }
+    );
+    let sold_before = fetch_server_sold(&config.address);
+    let start = Instant::now();
+
+    let buyer_reports = if config.deterministic {
+        run_deterministic(config)
+    } else {
+        run_concurrent(config)
+    };
+    let elapsed = start.elapsed();
+    let sold_after = fetch_server_sold(&config.address);
+
+    let mut latencies: HashMap<&'static str, Vec<Duration>> = HashMap::new();
+    let mut reserved = Vec::new();
+    let mut purchased = Vec::new();
+    let mut report = LoadReport {
+        elapsed,
+        buyers: config.buyers,
+        requests: 0,
+        errors: 0,
+        not_found: 0,
+        conflicts: 0,
+        reserved: 0,
+        purchased: 0,
+        latencies: HashMap::new(),
+        collisions: vec![],
+        oversold: vec![],
+        server_sold: None,
+    };
+
+    for buyer in buyer_reports {
+        report.errors += buyer.errors;
+        report.not_found += buyer.not_found;
+        report.conflicts += buyer.conflicts;
+        for (command, values) in buyer.latencies {
+            report.requests += values.len();
+            latencies.entry(command).or_default().extend(values);
+        }
+        reserved.extend(buyer.reserved);
+        purchased.extend(buyer.purchased);
+    }
+
+    for values in latencies.values_mut() {
+        values.sort();
+    }
+    report.reserved = reserved.len();
+    report.purchased = purchased.len();
+    report.latencies = latencies;
+    // Un asiento reservado o comprado por más de un comprador indica un conflicto en el servidor
+    report.collisions = repeated(reserved);
+    report.oversold = repeated(purchased);
+    // Si cada compra es única, el servidor debe haber vendido tantos asientos como compras completaron los compradores
+    report.server_sold = sold_before.zip(sold_after).map(|(before, after)| after.saturating_sub(before));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{self, ServerConfig};
+    use std::net::TcpListener;
+
+    fn start_server() -> String {
+        // Servidor nuevo en un puerto libre, sin canales extra ni archivo de estado
+        let address = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("Failed to find a free port")
+            .to_string();
+        let mut config = ServerConfig::new();
+        config.address = address.clone();
+        config.admin_address = None;
+        config.http_address = None;
+        config.ws_address = None;
+        config.metrics_address = None;
+        thread::spawn(move || server::start_server_with(config));
+        address
+    }
+
+    // Búsquedas sin asientos, conflictos, asientos reservados y comprados de un comprador
+    type BuyerOutcome = (usize, usize, Vec<(String, String, u32)>, Vec<(String, String, u32)>);
+
+    fn replay(seed: u64) -> Vec<BuyerOutcome> {
+        // Resultado de cada comprador de una simulación determinista contra un servidor recién iniciado
+        let mut config = LoadConfig::new();
+        config.address = start_server();
+        config.buyers = 4;
+        config.requests_per_buyer = 3;
+        config.seed = seed;
+        config.deterministic = true;
+        run_deterministic(&config)
+            .into_iter()
+            .map(|report| {
+                assert_eq!(report.errors, 0);
+                (report.not_found, report.conflicts, report.reserved, report.purchased)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deterministic_run_replays_with_the_same_seed() {
+        let first = replay(7);
+        assert_eq!(first, replay(7));
+        let purchased: Vec<(String, String, u32)> = first.into_iter().flat_map(|(_, _, _, purchased)| purchased).collect();
+        assert!(!purchased.is_empty());
+        assert!(repeated(purchased).is_empty());
+    }
+
+    #[test]
+    fn connection_reads_one_reply_per_request() {
+        let mut connection = connect(&start_server()).expect("Failed to connect");
+        assert_eq!(connection.send("ping").unwrap(), "PONG\n");
+        let map = connection.send("map").unwrap();
+        assert!(map.contains("ZonaC"));
+        assert!(!map.contains("END"));
+        // La respuesta de varias líneas no se mezcla con la siguiente
+        assert_eq!(connection.send("ping").unwrap(), "PONG\n");
+        let find = connection.send("find VIP 5 zones=ZonaA").unwrap();
+        assert!(find.starts_with("ERR NO_SEATS_FOUND"));
+        assert_eq!(connection.send("ping").unwrap(), "PONG\n");
+    }
+
+    #[test]
+    fn parse_seats_drops_the_category() {
+        assert_eq!(
+            parse_seats("(ZonaA,2) (ZonaB,3) (VIP,2)"),
+            vec![("ZonaA".to_string(), 2), ("ZonaB".to_string(), 3)]
+        );
+        assert!(parse_seats("").is_empty());
+    }
+}
@@ -1,16 +1,89 @@
+// En `server` y `client` la documentación de cada función va dentro de su cuerpo,
+// como comentario `/** */`; rustc no la asocia a nada y la marcaría como sin usar
+#[allow(unused_doc_comments)]
 mod server;
+#[allow(unused_doc_comments)]
 mod client;
 
+use client::simulation::{self, LoadConfig};
+
 fn main() {
-    // Ejecuta el servidor en un hilo separado
-    std::thread::spawn(|| {
-        server::start_server();
-    });
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    // Ejecuta el cliente en el hilo principal
-    client::run_client();
+    // Solo el servidor: cargo run -- server --workers 8 --max-connections 16
+    if args.first().map(String::as_str) == Some("server") {
+        match server::ServerConfig::from_args(&args[1..]) {
+            Ok(config) => server::start_server_with(config),
+            Err(e) => eprintln!("Invalid server options: {}", e),
+        }
+        return;
+    }
 
-    //mostrar el SeatingStructure
+    // Verificación de boletos sin conexión: cargo run -- verify --secret <clave> <codigo>
+    if args.first().map(String::as_str) == Some("verify") {
+        match args.get(1..4) {
+            Some([flag, secret, code]) if flag == "--secret" => {
+                match server::tickets::verify_code(secret.as_bytes(), code) {
+                    Ok(claims) => println!("Valid ticket: {:?}", claims),
+                    Err(e) => println!("Invalid ticket: {}", e),
+                }
+            }
+            _ => eprintln!("Usage: verify --secret <secret> <code>"),
+        }
+        return;
+    }
 
+    match args.first().map(String::as_str) {
+        // Prueba de carga: cargo run -- load --buyers 20 --seed 7 [--deterministic] [--addr host:puerto]
+        Some("load") => match LoadConfig::from_args(&args[1..]) {
+            Ok(config) => {
+                // Con `--addr` se prueba un servidor que ya está corriendo, sin `--addr` uno propio solo con TCP
+                if !args[1..].iter().any(|arg| arg == "--addr") {
+                    let mut server_config = server::ServerConfig::new();
+                    server_config.address = config.address.clone();
+                    server_config.admin_address = None;
+                    server_config.http_address = None;
+                    server_config.ws_address = None;
+                    server_config.metrics_address = None;
+                    std::thread::spawn(move || server::start_server_with(server_config));
+                }
+                let report = simulation::run_load(&config);
+                report.print();
+                // Código de salida distinto de cero para que la integración continua detecte la sobreventa
+                if !report.oversell_ok() {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => eprintln!("Invalid load test options: {}", e),
+        },
+        // Cliente con semilla fija: cargo run -- client --seed 7
+        Some("client") => {
+            spawn_server();
+            match args.get(1..3) {
+                Some([flag, seed]) if flag == "--seed" => match seed.parse::<u64>() {
+                    Ok(seed) => client::run_seeded_client(seed),
+                    Err(_) => eprintln!("Invalid seed: {}", seed),
+                },
+                _ => client::run_client(),
+            }
+        }
+        // Mapa en vivo: cargo run -- watch [VIP|General] [zona...]
+        Some("watch") => {
+            spawn_server();
+            client::watch_seat_map(&args[1..]);
+        }
+        // Ejecuta el cliente en el hilo principal y al final muestra el SeatingStructure
+        _ => {
+            spawn_server();
+            client::run_client();
+            client::show_seat_map();
+        }
+    }
+}
 
+fn spawn_server() {
+    // Ejecuta el servidor en un hilo separado
+    std::thread::spawn(|| {
+        server::start_server();
+    });
 }
@@ -1,7 +1,49 @@
-use std::io::{Read, Write};
+mod access;
+mod admin;
+mod error;
+mod http;
+mod health;
+mod logging;
+mod metrics;
+mod notify;
+mod orders;
+mod pool;
+mod render;
+mod search;
+mod stats;
+mod storage;
+pub mod tickets;
+mod waitlist;
+mod websocket;
+
+use access::SeatFeatures;
+use error::{SeatRef, TicketingError};
+use logging::{Level, LogConfig};
+use notify::Notifier;
+use orders::{OrderBook, RefundPolicy};
+use pool::{ConnectionSlot, ThreadPool};
+use render::MapOptions;
+use search::FindConstraints;
+use storage::StorageStatus;
+use serde_json::json;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::net::{TcpListener, TcpStream};
-use std::sync::{Arc, Mutex};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::thread;
+use std::time::{Duration, Instant};
+use waitlist::Waitlist;
+
+// Cada cuánto se revisa si se pidió cerrar el servidor mientras se espera una conexión o un comando
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Línea con la que terminan las respuestas de varias líneas (map, stats), el cliente lee hasta encontrarla
+pub const END_MARKER: &str = "END";
+
+// Largo máximo de una petición TCP, sin contar el salto de línea que la termina
+const MAX_REQUEST_LINE: usize = 1024;
 
 
 #[derive(Clone, PartialEq, Debug)]
@@ -11,10 +53,12 @@ pub enum SeatStatus {
     - Free: Asiento libre.
     - Reserved: Asiento reservado.
     - Purchased: Asiento comprado.
+    - Blocked: Asiento bloqueado por un operador, no se puede vender.
     */
     Free,
     Reserved,
     Purchased,
+    Blocked,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -24,10 +68,16 @@ pub struct Seat {
     - `status`: Estado del asiento (`SeatStatus`).
     - `vision_percentage`: Porcentaje de visión del asiento.
     - `number`: Número del asiento.
+    - `price`: Precio del asiento.
+    - `holder`: Conexión que reservó o compró el asiento, `None` si está libre.
+    - `features`: Atributos de accesibilidad del asiento.
     */
     pub status: SeatStatus,
     pub vision_percentage: u8,
     pub number: u32,
+    pub price: u32,
+    pub holder: Option<u64>,
+    pub features: SeatFeatures,
 }
 
 #[derive(Clone, Debug)]
@@ -47,6 +97,8 @@ pub struct General {
 }
 
 
+// Los campos llevan el nombre de la categoría tal como aparece en el protocolo
+#[allow(non_snake_case)]
 #[derive(Clone, Debug)]
 pub struct Zone {
     /**
@@ -70,9 +122,40 @@ pub struct Category {
 
 #[derive(Debug)]
 pub struct SeatingStructure {
+    /**
+    Estructura completa del lugar:
+    - `categories`: Categorías con sus zonas y asientos.
+    - `closed_sales`: Tipos de asiento ("VIP" o "General") con la venta cerrada.
+    - `orders`: Compras registradas, se actualizan junto con los asientos.
+    - `waitlist`: Clientes esperando asientos de un tipo agotado.
+    - `notifier`: Conexiones suscritas a los cambios de asientos.
+    - `storage`: Resultado de cargar el estado guardado al iniciar.
+    - `started_at`: Momento en que se creó el estado, para calcular el tiempo en marcha.
+    */
     pub categories: Vec<Category>,
+    pub closed_sales: Vec<String>,
+    pub orders: OrderBook,
+    pub waitlist: Waitlist,
+    pub notifier: Notifier,
+    pub storage: StorageStatus,
+    pub started_at: Instant,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeatFailure {
+    /**
+    Asiento de una selección que no se pudo reservar:
+    - `zone`, `number`: Asiento pedido.
+    - `reason`: Motivo, por ejemplo "purchased" o "not found".
+    */
+    pub zone: String,
+    pub number: u32,
+    pub reason: String,
 }
 
+// Máximo de asientos por búsqueda o selección
+const MAX_SEATS_PER_REQUEST: usize = 5;
+
 impl SeatingStructure {
     // Constructor qu inicializa la estrutura de asientos
     pub fn new() -> Self {
@@ -90,26 +173,41 @@ impl SeatingStructure {
                                     status: SeatStatus::Purchased,
                                     vision_percentage: 100,
                                     number: 1,
+                                    price: 150,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 100,
                                     number: 2,
+                                    price: 150,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 100,
                                     number: 3,
+                                    price: 150,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Purchased,
                                     vision_percentage: 100,
                                     number: 4,
+                                    price: 150,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Reserved,
                                     vision_percentage: 100,
                                     number: 5,
+                                    price: 150,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, ..SeatFeatures::default() },
                                 },
                             ],
                         },
@@ -119,26 +217,41 @@ impl SeatingStructure {
                                     status: SeatStatus::Free,
                                     vision_percentage: 80,
                                     number: 1,
+                                    price: 80,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Reserved,
                                     vision_percentage: 80,
                                     number: 2,
+                                    price: 80,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Reserved,
                                     vision_percentage: 80,
                                     number: 3,
+                                    price: 80,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 80,
                                     number: 4,
+                                    price: 80,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Reserved,
                                     vision_percentage: 80,
                                     number: 5,
+                                    price: 80,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, ..SeatFeatures::default() },
                                 },
                             ],
                         },
@@ -152,26 +265,41 @@ impl SeatingStructure {
                                     status: SeatStatus::Free,
                                     vision_percentage: 95,
                                     number: 1,
+                                    price: 120,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 95,
                                     number: 2,
+                                    price: 120,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 95,
                                     number: 3,
+                                    price: 120,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 95,
                                     number: 4,
+                                    price: 120,
+                                    holder: None,
+                                    features: SeatFeatures::default(),
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 95,
                                     number: 5,
+                                    price: 120,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, ..SeatFeatures::default() },
                                 },
                             ],
                         },
@@ -181,26 +309,41 @@ impl SeatingStructure {
                                     status: SeatStatus::Free,
                                     vision_percentage: 75,
                                     number: 1,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { wheelchair: true, aisle: true, restricted_view: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 75,
                                     number: 2,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { companion: true, restricted_view: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 75,
                                     number: 3,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { restricted_view: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 75,
                                     number: 4,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { restricted_view: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 75,
                                     number: 5,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, restricted_view: true, ..SeatFeatures::default() },
                                 },
                             ],
                         },
@@ -214,26 +357,41 @@ impl SeatingStructure {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 1,
+                                    price: 100,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, near_exit: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 2,
+                                    price: 100,
+                                    holder: None,
+                                    features: SeatFeatures { near_exit: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 3,
+                                    price: 100,
+                                    holder: None,
+                                    features: SeatFeatures { near_exit: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 4,
+                                    price: 100,
+                                    holder: None,
+                                    features: SeatFeatures { near_exit: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 5,
+                                    price: 100,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, near_exit: true, ..SeatFeatures::default() },
                                 },
                             ],
                         },
@@ -243,26 +401,41 @@ impl SeatingStructure {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 1,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { aisle: true, near_exit: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 2,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { near_exit: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 3,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { near_exit: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 4,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { companion: true, near_exit: true, ..SeatFeatures::default() },
                                 },
                                 Seat {
                                     status: SeatStatus::Free,
                                     vision_percentage: 90,
                                     number: 5,
+                                    price: 60,
+                                    holder: None,
+                                    features: SeatFeatures { wheelchair: true, aisle: true, near_exit: true, ..SeatFeatures::default() },
                                 },
                             ],
                         },
@@ -270,203 +443,982 @@ impl SeatingStructure {
                 ],
             },
         ];
-        SeatingStructure { categories }
+        SeatingStructure {
+            categories,
+            closed_sales: vec![],
+            orders: OrderBook::default(),
+            waitlist: Waitlist::default(),
+            notifier: Notifier::default(),
+            storage: StorageStatus::default(),
+            started_at: Instant::now(),
+        }
     }
 
-    pub fn find_free_seats(&self, typ: &str, seat_count: u32) -> Vec<(String, u32)> {
+    pub fn find_free_seats(
+        &self,
+        typ: &str,
+        seat_count: u32,
+        constraints: &FindConstraints,
+    ) -> Result<Vec<(String, u32)>, TicketingError> {
         /**
         Busca asientos libres en un categoria especifica.
         Solo se permite un maxima de 5 asientos para buscar
 
-        Retorna una combinaicon de asientos pueden estar en la misma zona o en otra
+        Retorna una combinaicon de asientos pueden estar en la misma zona o en otra,
+        el último elemento es el tipo y la cantidad pedidos.
 
          Parámetros
         - `typ`: Tipo de asiento ("VIP" o "General").
         - `seat_count`: Cantidad de asientos requeridos.
+        - `constraints`: Restricciones de la búsqueda (zona, visión, asientos juntos).
 
         */
-        // Verificar si seat_count es mayor que 5
-        if seat_count > 5 {
-            eprintln!("Error: Cannot exceed a seat count greater than 5.");
-            return vec![];
-        }
-
-        let mut best_seat_combination: Vec<(String, u32)> = Vec::new();
-        let mut best_vision_percentage = 0;
-        let mut best_distance = usize::MAX;
-
-        let categories = &self.categories;
-        let mut found_seats = false;  // Bandera para verificar si se encontraron asientos
-
-        for category in categories {
-            for zone in &category.zones {
-                let num_zone = match zone.name.as_str() {
-                    "ZonaA" => 0,
-                    "ZonaB" => 1,
-                    "ZonaC" => 2,
-                    _ => {
-                        eprintln!("Invalid zone: {}", zone.name);
-                        continue;
-                    }
-                };
+        let mut best_seat_combination = self.find_seats(typ, seat_count, constraints)?;
 
-                let seats = match typ {
-                    "VIP" => &zone.Vip.seats,
-                    "General" => &zone.General.seats,
-                    _ => {
-                        eprintln!("Invalid category: {}", typ);
-                        return vec![];
-                    }
-                };
+        // Si no se encontraron asientos, el cliente recibe el error con las alternativas
+        if best_seat_combination.is_empty() {
+            return Err(TicketingError::NoSeatsFound);
+        }
 
-                let mut free_seats = 0;
-                let mut vision_percentage = 0;
-                let mut distance = 0;
-                let mut seat_combination: Vec<(String, u32)> = Vec::new();
-
-                for seat in seats {
-                    if free_seats == seat_count {
-                        // Compara y guarda la mejor combinación de asientos encontrados
-                        if best_distance > distance {
-                            best_distance = distance;
-                            if best_vision_percentage < vision_percentage {
-                                best_vision_percentage = vision_percentage;
-                                best_seat_combination = seat_combination.clone();
-                            } else if best_vision_percentage == vision_percentage {
-                                if best_distance > distance {
-                                    best_seat_combination = seat_combination.clone();
-                                }
-                            }
-                        }
-                        found_seats = true;  // Asientos encontrados en la zona
-                    }
+        // Agregar la categoría al final de la mejor combinación
+        best_seat_combination.push((typ.to_string(), seat_count));
+        Ok(best_seat_combination)
+    }
 
-                    if seat.status == SeatStatus::Free {
-                        free_seats += 1;
-                        vision_percentage += seat.vision_percentage as u32;
-                        if free_seats > 1 {
-                            distance += 1;
-                        }
-                        seat_combination.push((zone.name.clone(), seat.number));
-                    } else {
-                        if free_seats > 1 {
-                            distance += 1;
+    pub fn set_seats_status(
+        &mut self,
+        typ: &str,
+        seats: &[(String, u32)],
+        status: SeatStatus,
+        holder: Option<u64>,
+    ) -> Result<(), TicketingError> {
+        /**
+        Cambia el estado de varios asientos de un mismo tipo en una sola operación.
+        El tipo se valida antes de tocar cualquier asiento, así nunca queda
+        un cambio a medias.
+        */
+        if typ != "VIP" && typ != "General" {
+            return Err(TicketingError::InvalidCategory(typ.to_string()));
+        }
+
+        for category in &mut self.categories {
+            for zone in &mut category.zones {
+                let zone_seats = match typ {
+                    "VIP" => &mut zone.Vip.seats,
+                    _ => &mut zone.General.seats,
+                };
+                for seat in zone_seats {
+                    if seats.iter().any(|(name, number)| *name == zone.name && *number == seat.number) {
+                        if status == SeatStatus::Reserved && seat.status != SeatStatus::Reserved {
+                            metrics::holds_created(1);
                         }
+                        seat.status = status.clone();
+                        seat.holder = holder;
                     }
                 }
+            }
+        }
+        Ok(())
+    }
 
-                // Manejo de zonas adicionales si no se ha encontrado suficientes asientos
-                while free_seats < seat_count {
-                    let next_zone = (num_zone + 1) % category.zones.len();
-                    let seats = match typ {
-                        "VIP" => &category.zones[next_zone].Vip.seats,
-                        "General" => &category.zones[next_zone].General.seats,
-                        _ => {
-                            eprintln!("Invalid category: {}", typ);
-                            return vec![];
-                        }
-                    };
+    pub fn seat(&self, typ: &str, zone_name: &str, number: u32) -> Option<&Seat> {
+        /**
+        Busca un asiento por tipo, zona y número, solo para leerlo
+        */
+        self.categories
+            .iter()
+            .flat_map(|category| category.zones.iter())
+            .filter(|zone| zone.name == zone_name)
+            .flat_map(|zone| match typ {
+                "VIP" => zone.Vip.seats.iter(),
+                "General" => zone.General.seats.iter(),
+                _ => [].iter(),
+            })
+            .find(|seat| seat.number == number)
+    }
 
-                    for seat in seats {
-                        if free_seats == seat_count {
-                            if best_distance > distance {
-                                best_distance = distance;
-                                if best_vision_percentage < vision_percentage {
-                                    best_vision_percentage = vision_percentage;
-                                    best_seat_combination = seat_combination.clone();
-                                } else if best_vision_percentage == vision_percentage {
-                                    if best_distance > distance {
-                                        best_seat_combination = seat_combination.clone();
-                                    }
-                                }
-                            }
-                            found_seats = true;  // Asientos encontrados en la siguiente zona
-                        }
+    pub fn seat_mut(&mut self, typ: &str, zone_name: &str, number: u32) -> Option<&mut Seat> {
+        /**
+        Busca un asiento por tipo, zona y número
+        */
+        self.categories
+            .iter_mut()
+            .flat_map(|category| category.zones.iter_mut())
+            .filter(|zone| zone.name == zone_name)
+            .flat_map(|zone| match typ {
+                "VIP" => zone.Vip.seats.iter_mut(),
+                "General" => zone.General.seats.iter_mut(),
+                _ => [].iter_mut(),
+            })
+            .find(|seat| seat.number == number)
+    }
 
-                        if seat.status == SeatStatus::Free {
-                            free_seats += 1;
-                            vision_percentage += seat.vision_percentage as u32;
-                            if free_seats > 1 {
-                                distance += 1;
-                            }
-                            seat_combination.push((category.zones[next_zone].name.clone(), seat.number));
-                        } else {
-                            if free_seats > 1 {
-                                distance += 1;
-                            }
+    pub fn sales_open(&self, typ: &str) -> bool {
+        // La venta está abierta salvo que un operador la haya cerrado
+        !self.closed_sales.iter().any(|closed| closed == typ)
+    }
+
+    pub fn reload_venue(&mut self, mut venue: SeatingStructure) {
+        /**
+        Reemplaza la distribución del lugar por una nueva (visión, precios, zonas)
+        conservando el estado y el dueño de los asientos que existen en ambas.
+        */
+        for category in &mut venue.categories {
+            for zone in &mut category.zones {
+                for (typ, seats) in [("VIP", &mut zone.Vip.seats), ("General", &mut zone.General.seats)] {
+                    for seat in seats {
+                        if let Some(current) = self.seat_mut(typ, &zone.name, seat.number) {
+                            seat.status = current.status.clone();
+                            seat.holder = current.holder;
                         }
                     }
                 }
             }
         }
+        self.categories = venue.categories;
+        // Una oferta con asientos que ya no existen se retira
+        self.withdraw_stale_offers();
+        self.offer_freed_seats();
+    }
+
+    pub fn purchase_seat(&mut self, typ: &str, zone_name: &str, number: u32, holder: u64) -> Result<u64, TicketingError> {
+        /**
+        Marca un asiento como comprado y registra la orden con su precio y su boleto.
+        Solo se compra un asiento libre o uno reservado por la misma conexión,
+        así nunca quedan dos órdenes vigentes para el mismo asiento.
+        Retorna el número de orden.
+        */
+        // Sin el estado guardado no se sabe qué asientos ya se vendieron
+        if let StorageStatus::Failed { path, .. } = &self.storage {
+            return Err(TicketingError::StorageUnavailable(path.clone()));
+        }
+        if typ != "VIP" && typ != "General" {
+            return Err(TicketingError::InvalidCategory(typ.to_string()));
+        }
+        let seat = self
+            .seat_mut(typ, zone_name, number)
+            .ok_or_else(|| TicketingError::SeatNotFound(SeatRef::new(typ, zone_name, number)))?;
+        match seat.status {
+            SeatStatus::Free => {}
+            SeatStatus::Reserved if seat.holder == Some(holder) => {}
+            _ => return Err(TicketingError::SeatNotFree(SeatRef::new(typ, zone_name, number))),
+        }
+        // También con el espacio ya reservado: el acompañante pudo liberarse después
+        self.check_wheelchair_booking(typ, zone_name, number, holder)?;
+        let seat = self
+            .seat_mut(typ, zone_name, number)
+            .ok_or_else(|| TicketingError::SeatNotFound(SeatRef::new(typ, zone_name, number)))?;
+        seat.status = SeatStatus::Purchased;
+        seat.holder = Some(holder);
+        let price = seat.price;
+        metrics::seat_sold(typ, price);
+        Ok(self.orders.record(holder, typ, zone_name, number, price))
+    }
+
+    pub fn refund_order(&mut self, order_id: u64, holder: u64) -> Result<u32, TicketingError> {
+        /**
+        Reembolsa una orden de la misma conexión si la política lo permite.
+        El asiento vuelve a estar libre y el monto queda registrado en la orden.
 
-        // Si no se encontraron asientos, retorna un mensaje de error
-        if !found_seats {
-            eprintln!("No suitable seats found.");
-            return vec![];
+        Retorna el monto reembolsado.
+        */
+        let order = self.orders.order(order_id).ok_or(TicketingError::OrderNotFound(order_id))?;
+        if order.holder != Some(holder) {
+            return Err(TicketingError::OrderNotOwned(order_id));
         }
+        let refunded_at = orders::now();
+        let amount = self.orders.refund_amount(order, refunded_at)?;
+        let (typ, zone, number) = (order.typ.clone(), order.zone.clone(), order.number);
 
-        // Agregar la categoría al final de la mejor combinación
-        best_seat_combination.push((typ.to_string(), seat_count));
-        best_seat_combination
+        if let Some(seat) = self.seat_mut(&typ, &zone, number) {
+            if seat.status == SeatStatus::Purchased {
+                seat.status = SeatStatus::Free;
+                seat.holder = None;
+            }
+        }
+        if let Some(order) = self.orders.order_mut(order_id) {
+            order.refund = Some(orders::Refund { amount, refunded_at });
+        }
+        self.offer_freed_seats();
+        Ok(amount)
+    }
+
+    pub fn refund_seat(&mut self, typ: &str, zone_name: &str, number: u32, holder: u64) -> Result<(u64, u32), TicketingError> {
+        /**
+        Reembolsa la orden vigente de un asiento.
+        Retorna el número de orden y el monto reembolsado.
+        */
+        let order_id = self
+            .orders
+            .seat_order_id(typ, zone_name, number)
+            .ok_or_else(|| TicketingError::NothingToRefund(SeatRef::new(typ, zone_name, number)))?;
+        let amount = self.refund_order(order_id, holder)?;
+        Ok((order_id, amount))
     }
 
+    pub fn release_seat(&mut self, typ: &str, zone_name: &str, number: u32, holder: u64) -> Result<(), TicketingError> {
+        /**
+        Devuelve a libre un asiento reservado, solo si lo reservó la misma conexión.
+
+        Retorna un error si el asiento no existe, no está reservado
+        o lo reservó otra conexión.
+        */
+        let seat = self
+            .seat_mut(typ, zone_name, number)
+            .ok_or_else(|| TicketingError::SeatNotFound(SeatRef::new(typ, zone_name, number)))?;
+        if seat.status != SeatStatus::Reserved || seat.holder != Some(holder) {
+            return Err(TicketingError::SeatNotHeld(SeatRef::new(typ, zone_name, number)));
+        }
+        seat.status = SeatStatus::Free;
+        seat.holder = None;
+        self.offer_freed_seats();
+        Ok(())
+    }
+
+    pub fn operator_release(&mut self, typ: &str, zone_name: &str, number: u32) -> Result<Option<(u64, u32)>, TicketingError> {
+        /**
+        Libera un asiento desde el canal de administración, en cualquier estado.
+        Si estaba comprado, su orden vigente se reembolsa completa para que el boleto
+        deje de valer antes de revender el asiento.
+
+        Retorna la orden reembolsada y el monto, si la había.
+        */
+        let seat = self
+            .seat_mut(typ, zone_name, number)
+            .ok_or_else(|| TicketingError::SeatNotFound(SeatRef::new(typ, zone_name, number)))?;
+        let purchased = seat.status == SeatStatus::Purchased;
+        seat.status = SeatStatus::Free;
+        seat.holder = None;
+
+        let mut refunded = None;
+        if let Some(order_id) = self.orders.seat_order_id(typ, zone_name, number).filter(|_| purchased) {
+            if let Some(order) = self.orders.order_mut(order_id) {
+                order.refund = Some(orders::Refund { amount: order.price, refunded_at: orders::now() });
+                refunded = Some((order_id, order.price));
+            }
+        }
+        self.withdraw_stale_offers();
+        self.offer_freed_seats();
+        Ok(refunded)
+    }
+
+    pub fn hold_seat(&mut self, typ: &str, zone_name: &str, number: u32, holder: u64) -> Result<(), TicketingError> {
+        /**
+        Reserva un asiento para una conexión, solo si la venta del tipo está abierta
+        y el asiento está libre; nunca pisa otra reserva o compra. Si la misma conexión
+        ya lo tenía reservado no cambia nada.
+        Un espacio para silla de ruedas requiere tener antes su acompañante contiguo.
+        */
+        if !self.sales_open(typ) {
+            return Err(TicketingError::SalesClosed(typ.to_string()));
+        }
+        self.check_wheelchair_booking(typ, zone_name, number, holder)?;
+        let seat = self
+            .seat_mut(typ, zone_name, number)
+            .ok_or_else(|| TicketingError::SeatNotFound(SeatRef::new(typ, zone_name, number)))?;
+        match seat.status {
+            SeatStatus::Free => {}
+            SeatStatus::Reserved if seat.holder == Some(holder) => return Ok(()),
+            _ => return Err(TicketingError::SeatNotFree(SeatRef::new(typ, zone_name, number))),
+        }
+        seat.status = SeatStatus::Reserved;
+        seat.holder = Some(holder);
+        metrics::holds_created(1);
+        Ok(())
+    }
+
+    pub fn hold_seats(&mut self, typ: &str, seats: &[(String, u32)], holder: u64) -> Result<(), TicketingError> {
+        /**
+        Reserva una selección de asientos elegida por el cliente, todos o ninguno.
+        Primero revisa cada asiento y, si alguno falla, no reserva nada y retorna
+        el motivo de cada asiento que falló.
+
+        Un asiento ya reservado por la misma conexión cuenta como disponible, y un
+        espacio para silla de ruedas necesita un acompañante contiguo en la selección
+        o ya reservado por la conexión.
+        */
+        if typ != "VIP" && typ != "General" {
+            return Err(TicketingError::InvalidCategory(typ.to_string()));
+        }
+        if !self.sales_open(typ) {
+            return Err(TicketingError::SalesClosed(typ.to_string()));
+        }
+        if seats.is_empty() || seats.len() > MAX_SEATS_PER_REQUEST {
+            return Err(TicketingError::InvalidSeatCount);
+        }
+
+        let mut failures = Vec::new();
+        for (index, (zone, number)) in seats.iter().enumerate() {
+            let reason = if seats[..index].contains(&(zone.clone(), *number)) {
+                Some("duplicate")
+            } else {
+                match self.seat(typ, zone, *number) {
+                    None => Some("not found"),
+                    Some(seat) => match seat.status {
+                        SeatStatus::Free => None,
+                        SeatStatus::Reserved if seat.holder == Some(holder) => None,
+                        SeatStatus::Reserved => Some("reserved"),
+                        SeatStatus::Purchased => Some("purchased"),
+                        SeatStatus::Blocked => Some("blocked"),
+                    },
+                }
+            };
+            if let Some(reason) = reason {
+                failures.push(SeatFailure { zone: zone.clone(), number: *number, reason: reason.to_string() });
+            }
+        }
+        if !failures.is_empty() {
+            return Err(TicketingError::SeatsUnavailable(failures));
+        }
+
+        // Los acompañantes se revisan cuando ya se sabe que todos los asientos existen
+        let failures: Vec<SeatFailure> = seats
+            .iter()
+            .filter(|(zone, number)| self.missing_companion(typ, zone, *number, holder, seats))
+            .map(|(zone, number)| SeatFailure {
+                zone: zone.clone(),
+                number: *number,
+                reason: "wheelchair space needs an adjacent companion seat".to_string(),
+            })
+            .collect();
+        if !failures.is_empty() {
+            return Err(TicketingError::SeatsUnavailable(failures));
+        }
+        self.set_seats_status(typ, seats, SeatStatus::Reserved, Some(holder))
+    }
+
+    pub fn release_holds(&mut self, holder: u64) -> usize {
+        /**
+        Libera los asientos reservados por una conexión que no llegaron a comprarse
+        y los ofrece a la lista de espera. Retorna la cantidad de asientos liberados.
+        No cuenta en `holds_expired`: eso lo decide quien llama, `release all` no vence nada.
+        */
+        let mut released = 0;
+        for category in &mut self.categories {
+            for zone in &mut category.zones {
+                for seat in zone.Vip.seats.iter_mut().chain(zone.General.seats.iter_mut()) {
+                    if seat.status == SeatStatus::Reserved && seat.holder == Some(holder) {
+                        seat.status = SeatStatus::Free;
+                        seat.holder = None;
+                        released += 1;
+                    }
+                }
+            }
+        }
+        if released > 0 {
+            self.offer_freed_seats();
+        }
+        released
+    }
+
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /**
+    Configuración del servidor:
+    - `address`: Dirección donde escucha el servidor.
+    - `workers`: Cantidad de hilos que atienden conexiones.
+    - `max_connections`: Máximo de conexiones activas, las que superan el límite reciben
+      `ERR SERVER_BUSY`. Cada conexión ocupa un hilo mientras está abierta, así que nunca
+      se aceptan más que `workers` aunque se configure un valor mayor.
+    - `state_file`: Archivo donde se carga y guarda el estado de los asientos.
+    - `shutdown_timeout`: Tiempo máximo que se espera a las conexiones al cerrar.
+    - `venue_file`: Archivo con la distribución del lugar, se puede recargar desde el canal de administración.
+    - `admin_address`: Dirección del canal de administración, `None` lo desactiva.
+    - `admin_token`: Clave que deben enviar los operadores con `auth <clave>`.
+      El canal de administración no arranca hasta que se configura una.
+    - `refund_policy`: Política de reembolso de las compras.
+    - `event`: Nombre del evento que va en los boletos.
+    - `ticket_secret`: Clave para firmar los boletos, si no se configura se genera una al azar
+      y los boletos no se pueden verificar después de reiniciar el servidor.
+    - `claim_window`: Tiempo que tiene un cliente de la lista de espera para reclamar su oferta.
+    - `http_address`: Dirección de la API HTTP/JSON, `None` la desactiva.
+    - `http_hold_timeout`: Tiempo sin peticiones tras el cual se liberan las reservas de una sesión HTTP.
+    - `ws_address`: Dirección del WebSocket con el mapa en vivo, `None` lo desactiva.
+    - `log`: Nivel y destino del registro del servidor (stderr o un archivo que rota).
+    - `metrics_address`: Dirección donde se exportan las métricas para Prometheus, `None` la desactiva.
+    */
+    pub address: String,
+    pub workers: usize,
+    pub max_connections: usize,
+    pub state_file: Option<String>,
+    pub shutdown_timeout: Duration,
+    pub venue_file: Option<String>,
+    pub admin_address: Option<String>,
+    pub admin_token: Option<String>,
+    pub refund_policy: RefundPolicy,
+    pub event: String,
+    pub ticket_secret: Option<String>,
+    pub claim_window: Duration,
+    pub http_address: Option<String>,
+    pub http_hold_timeout: Duration,
+    pub ws_address: Option<String>,
+    pub log: LogConfig,
+    pub metrics_address: Option<String>,
+}
+
+impl ServerConfig {
+    pub fn new() -> Self {
+        ServerConfig {
+            address: "127.0.0.1:7878".to_string(),
+            workers: 32,
+            max_connections: 32,
+            state_file: None,
+            shutdown_timeout: Duration::from_secs(10),
+            venue_file: None,
+            admin_address: Some("127.0.0.1:7879".to_string()),
+            admin_token: None,
+            refund_policy: RefundPolicy::default(),
+            event: "event".to_string(),
+            ticket_secret: None,
+            claim_window: Duration::from_secs(60),
+            http_address: Some("127.0.0.1:8080".to_string()),
+            http_hold_timeout: Duration::from_secs(300),
+            ws_address: Some("127.0.0.1:8081".to_string()),
+            log: LogConfig::default(),
+            metrics_address: Some("127.0.0.1:9184".to_string()),
+        }
+    }
+
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        /**
+        Construye la configuración a partir de los argumentos de la línea de comandos.
+
+        Opciones:
+        - `--addr <host:puerto>`
+        - `--workers <n>`
+        - `--max-connections <n>`
+        - `--state <archivo>`
+        - `--shutdown-timeout <segundos>`
+        - `--venue <archivo>`
+        - `--admin-addr <host:puerto>` o `--admin-addr off`
+        - `--admin-token <clave>`
+        - `--no-refunds`
+        - `--refund-percent <porcentaje>`
+        - `--refund-deadline <segundos unix>`
+        - `--event <nombre>`
+        - `--ticket-secret <clave>`
+        - `--claim-window <segundos>`
+        - `--http-addr <host:puerto>` o `--http-addr off`
+        - `--http-hold-timeout <segundos>`
+        - `--ws-addr <host:puerto>` o `--ws-addr off`
+        - `--log-level <debug|info|warn|error>`
+        - `--log-file <archivo>`
+        - `--log-max-size <bytes>`
+        - `--log-keep <n>`
+        - `--metrics-addr <host:puerto>` o `--metrics-addr off`
+        */
+        let mut config = ServerConfig::new();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            if flag == "--no-refunds" {
+                config.refund_policy.enabled = false;
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {}", flag))?;
+            match flag.as_str() {
+                "--addr" => config.address = value.clone(),
+                "--workers" => {
+                    config.workers = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", flag, value))?
+                }
+                "--max-connections" => {
+                    config.max_connections = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", flag, value))?
+                }
+                "--state" => config.state_file = Some(value.clone()),
+                "--venue" => config.venue_file = Some(value.clone()),
+                "--admin-addr" if value == "off" => config.admin_address = None,
+                "--admin-addr" => config.admin_address = Some(value.clone()),
+                "--admin-token" => config.admin_token = Some(value.clone()),
+                "--http-addr" if value == "off" => config.http_address = None,
+                "--http-addr" => config.http_address = Some(value.clone()),
+                "--ws-addr" if value == "off" => config.ws_address = None,
+                "--ws-addr" => config.ws_address = Some(value.clone()),
+                "--http-hold-timeout" => {
+                    let seconds: u64 = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", flag, value))?;
+                    config.http_hold_timeout = Duration::from_secs(seconds);
+                }
+                "--refund-percent" => match value.parse::<u8>() {
+                    Ok(percentage) if percentage <= 100 => config.refund_policy.percentage = percentage,
+                    _ => return Err(format!("Invalid value for {}: {}", flag, value)),
+                },
+                "--event" => {
+                    // El nombre va dentro del código del boleto, separado por puntos
+                    if !value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                        return Err(format!("Invalid event name (letters, digits, - and _ only): {}", value));
+                    }
+                    config.event = value.clone();
+                }
+                "--ticket-secret" => config.ticket_secret = Some(value.clone()),
+                "--refund-deadline" => {
+                    let deadline = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", flag, value))?;
+                    config.refund_policy.deadline = Some(deadline);
+                }
+                "--claim-window" => {
+                    let seconds: u64 = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", flag, value))?;
+                    config.claim_window = Duration::from_secs(seconds);
+                }
+                "--shutdown-timeout" => {
+                    let seconds: u64 = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", flag, value))?;
+                    config.shutdown_timeout = Duration::from_secs(seconds);
+                }
+                "--log-level" => {
+                    config.log.level = Level::parse(value).ok_or_else(|| format!("Invalid value for {}: {}", flag, value))?
+                }
+                "--log-file" => config.log.file = Some(value.clone()),
+                "--log-max-size" => {
+                    config.log.max_size = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", flag, value))?
+                }
+                "--log-keep" => {
+                    config.log.keep = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", flag, value))?
+                }
+                "--metrics-addr" if value == "off" => config.metrics_address = None,
+                "--metrics-addr" => config.metrics_address = Some(value.clone()),
+                _ => return Err(format!("Unknown option: {}", flag)),
+            }
+        }
+
+        if config.workers == 0 {
+            return Err("Server needs at least one worker".to_string());
+        }
+        if config.max_connections == 0 {
+            return Err("Server needs to accept at least one connection".to_string());
+        }
+        Ok(config)
+    }
 }
 
 pub fn start_server() {
+    /**
+    Inicia el servidor TCP con la configuración por defecto
+    */
+    start_server_with(ServerConfig::new());
+}
+
+pub fn start_server_with(config: ServerConfig) {
     /**
     Inicia el servidor TCP, configurando el estado inical de los
-    asientos y manejando connexiones entrantrantes
+    asientos y manejando connexiones entrantrantes con un grupo fijo de hilos.
+
+    Con SIGINT o SIGTERM deja de aceptar conexiones, espera a que terminen los
+    comandos en curso, avisa a los clientes conectados y guarda el estado.
     */
-    let seating_structure = Arc::new(Mutex::new(SeatingStructure::new()));
+    if let Err(e) = logging::init(&config.log) {
+        eprintln!("Failed to open log file, logging to stderr: {}", e);
+    }
+    // El estado guardado tiene prioridad sobre la distribución del lugar
+    let initial_state = [config.state_file.as_deref(), config.venue_file.as_deref()];
+    let mut initial_structure = storage::load_or_default(&initial_state);
+    initial_structure.orders.refund_policy = config.refund_policy.clone();
+    initial_structure.orders.event = config.event.clone();
+    initial_structure.orders.ticket_secret = match &config.ticket_secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => {
+            logging::warn("No ticket secret configured, tickets can't be verified after a restart", json!({}));
+            tickets::new_secret()
+        }
+    };
+    initial_structure.waitlist.claim_window = config.claim_window;
+    let seating_structure = Arc::new(RwLock::new(initial_structure));
+    let pool = ThreadPool::new(config.workers);
+    // Una conexión inactiva sigue ocupando su hilo: más conexiones que hilos quedarían sin atender
+    let max_connections = config.max_connections.min(config.workers);
+    let active_connections = Arc::new(AtomicUsize::new(0));
+    // Los ids de conexión y de sesión HTTP salen del mismo contador, así nunca se repite un dueño
+    let next_connection_id = Arc::new(AtomicU64::new(1));
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let signal = Arc::clone(&shutdown);
+    if let Err(e) = ctrlc::set_handler(move || signal.store(true, Ordering::SeqCst)) {
+        logging::warn("Failed to install shutdown handler", json!({ "error": e.to_string() }));
+    }
+
+    let listener = TcpListener::bind(&config.address).expect("Failed to bind to address");
+    // Sin bloqueo, para poder revisar la señal de cierre entre conexiones
+    listener.set_nonblocking(true).expect("Failed to set listener to non-blocking");
+    logging::info(
+        "Server is listening",
+        json!({ "address": config.address, "workers": config.workers, "max_connections": max_connections }),
+    );
+
+    if let (Some(address), None) = (&config.admin_address, &config.admin_token) {
+        logging::warn("Admin channel disabled, set --admin-token to enable it", json!({ "address": address }));
+    } else if let Some(address) = &config.admin_address {
+        let result = admin::start_admin(
+            config.clone(),
+            address.clone(),
+            Arc::clone(&seating_structure),
+            Arc::clone(&shutdown),
+        );
+        if let Err(e) = result {
+            logging::error("Failed to start admin channel", json!({ "address": address, "error": e.to_string() }));
+        }
+    }
+
+    if let Some(address) = &config.http_address {
+        let result = http::start_http(
+            address.clone(),
+            Arc::clone(&seating_structure),
+            Arc::clone(&next_connection_id),
+            config.http_hold_timeout,
+            Arc::clone(&shutdown),
+        );
+        if let Err(e) = result {
+            logging::error("Failed to start HTTP API", json!({ "address": address, "error": e.to_string() }));
+        }
+    }
 
-    let listener = TcpListener::bind("127.0.0.1:7878").expect("Failed to bind to address");
-    println!("Server is listening on port 7878");
+    if let Some(address) = &config.ws_address {
+        let result = websocket::start_websocket(
+            address.clone(),
+            Arc::clone(&seating_structure),
+            Arc::clone(&next_connection_id),
+            Arc::clone(&shutdown),
+        );
+        if let Err(e) = result {
+            logging::error("Failed to start WebSocket", json!({ "address": address, "error": e.to_string() }));
+        }
+    }
+
+    if let Some(address) = &config.metrics_address {
+        if let Err(e) = metrics::start_metrics(address.clone(), Arc::clone(&shutdown)) {
+            logging::error("Failed to start metrics", json!({ "address": address, "error": e.to_string() }));
+        }
+    }
 
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
+    // Vence las ofertas de la lista de espera que no se reclamaron a tiempo
+    let expiring_structure = Arc::clone(&seating_structure);
+    let expiring_shutdown = Arc::clone(&shutdown);
+    thread::spawn(move || {
+        while !expiring_shutdown.load(Ordering::SeqCst) {
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+            if !read_seats(&expiring_structure).waitlist.offers.is_empty() {
+                write_seats(&expiring_structure).expire_offers(Instant::now());
+            }
+        }
+    });
+
+    while !shutdown.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                let slot = match ConnectionSlot::acquire(&active_connections, max_connections) {
+                    Some(slot) => slot,
+                    None => {
+                        // Servidor lleno, se rechaza la conexión sin ocupar un hilo
+                        logging::warn("Server busy, connection rejected", json!({ "max_connections": max_connections }));
+                        if let Err(e) = stream.write_all(format!("{}\n", TicketingError::ServerBusy.reply()).as_bytes()) {
+                            logging::warn("Failed to write to stream", json!({ "error": e.to_string() }));
+                        }
+                        continue;
+                    }
+                };
+                if let Err(e) = prepare_stream(&stream) {
+                    logging::warn("Failed to configure connection", json!({ "error": e.to_string() }));
+                    continue;
+                }
+                let id = next_connection_id.fetch_add(1, Ordering::SeqCst);
                 let seating_structure = Arc::clone(&seating_structure);
-                thread::spawn(move || {
-                    handle_client(stream, seating_structure);
+                let shutdown = Arc::clone(&shutdown);
+                pool.execute(move || {
+                    let _slot = slot;
+                    handle_client(stream, id, seating_structure, shutdown);
                 });
             }
-            Err(e) => eprintln!("Failed to accept connection: {}", e),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(SHUTDOWN_POLL_INTERVAL),
+            Err(e) => logging::warn("Failed to accept connection", json!({ "error": e.to_string() })),
+        }
+    }
+
+    logging::info("Shutting down, no longer accepting connections", json!({}));
+    drop(listener);
+
+    // Espera a que las conexiones terminen su comando actual y se cierren
+    let deadline = Instant::now() + config.shutdown_timeout;
+    while active_connections.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+        thread::sleep(SHUTDOWN_POLL_INTERVAL);
+    }
+
+    if let Some(path) = &config.state_file {
+        let seats = read_seats(&seating_structure);
+        // Un archivo que no se pudo cargar se deja intacto para recuperarlo a mano
+        if let StorageStatus::Failed { path: failed, .. } = &seats.storage {
+            logging::error("Seating state failed to load, not saving over it", json!({ "path": path, "failed": failed }));
+        } else {
+            match storage::save_state(&seats, path) {
+                Ok(()) => logging::info("Saved seating state", json!({ "path": path })),
+                Err(e) => logging::error("Failed to save seating state", json!({ "path": path, "error": e.to_string() })),
+            }
+        }
+    }
+
+    let remaining = active_connections.load(Ordering::SeqCst);
+    if remaining > 0 {
+        // No se espera a los hilos que siguen ocupados, el proceso termina igual
+        logging::error("Shutdown timeout reached", json!({ "open_connections": remaining }));
+        std::process::exit(1);
+    }
+    drop(pool);
+    logging::info("Server stopped", json!({}));
+    // Termina el proceso completo, también el cliente si corre en el mismo proceso
+    std::process::exit(0);
+}
+
+fn prepare_stream(stream: &TcpStream) -> io::Result<()> {
+    // La conexión bloquea en las lecturas, pero despierta seguido para revisar la señal de cierre
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(SHUTDOWN_POLL_INTERVAL))
+}
+
+fn read_seats(seating_structure: &RwLock<SeatingStructure>) -> RwLockReadGuard<'_, SeatingStructure> {
+    // Si otro hilo entró en pánico con el candado tomado, se recupera el estado en lugar de propagar el pánico
+    let started = Instant::now();
+    let guard = seating_structure.read().unwrap_or_else(|poisoned| {
+        logging::error("Seating structure lock was poisoned, recovering", json!({}));
+        seating_structure.clear_poison();
+        poisoned.into_inner()
+    });
+    metrics::lock_wait("read", started.elapsed());
+    guard
+}
+
+fn write_seats(seating_structure: &RwLock<SeatingStructure>) -> SeatsWriteGuard<'_> {
+    let started = Instant::now();
+    let guard = seating_structure.write().unwrap_or_else(|poisoned| {
+        logging::error("Seating structure lock was poisoned, recovering", json!({}));
+        seating_structure.clear_poison();
+        poisoned.into_inner()
+    });
+    metrics::lock_wait("write", started.elapsed());
+    // Solo se toma la foto de los asientos si alguien va a recibir los cambios
+    let before = guard.notifier.has_subscribers().then(|| notify::snapshot(&guard));
+    SeatsWriteGuard { guard, before }
+}
+
+struct SeatsWriteGuard<'a> {
+    /**
+    Candado de escritura de los asientos.
+    Al soltarlo publica a los suscriptores los asientos que cambiaron de estado,
+    sin importar qué comando (cliente u operador) hizo el cambio.
+    */
+    guard: RwLockWriteGuard<'a, SeatingStructure>,
+    before: Option<notify::SeatSnapshot>,
+}
+
+impl Deref for SeatsWriteGuard<'_> {
+    type Target = SeatingStructure;
+
+    fn deref(&self) -> &SeatingStructure {
+        &self.guard
+    }
+}
+
+impl DerefMut for SeatsWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut SeatingStructure {
+        &mut self.guard
+    }
+}
+
+impl Drop for SeatsWriteGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(before) = self.before.take() {
+            notify::publish_changes(&mut self.guard, &before);
+        }
+    }
+}
+
+struct ConnectionHolds {
+    /**
+    Asientos reservados por una conexión.
+    Al salir de alcance (desconexión, error o pánico) libera las reservas
+    que no se compraron y saca a la conexión de la lista de espera.
+    */
+    id: u64,
+    seating_structure: Arc<RwLock<SeatingStructure>>,
+}
+
+impl Drop for ConnectionHolds {
+    fn drop(&mut self) {
+        let mut seating_structure = write_seats(&self.seating_structure);
+        seating_structure.waitlist.leave(self.id);
+        seating_structure.notifier.unsubscribe(self.id);
+        let released = seating_structure.release_holds(self.id);
+        if released > 0 {
+            metrics::holds_expired(released);
+            logging::info("Released unpurchased seats", json!({ "conn": self.id, "released": released }));
+        }
+    }
+}
+
+fn parse_seat_list<'a>(args: impl Iterator<Item = &'a str>) -> Result<Vec<(String, u32)>, TicketingError> {
+    /**
+    Lee una lista de asientos: cada zona seguida de sus números o rangos,
+    por ejemplo `ZonaB 2 3 ZonaC 1-2`.
+    */
+    let mut seats = Vec::new();
+    let mut zone: Option<&str> = None;
+    for arg in args {
+        let range = match arg.split_once('-') {
+            Some((first, last)) => first.parse::<u32>().ok().zip(last.parse::<u32>().ok()),
+            None => arg.parse::<u32>().ok().map(|number| (number, number)),
+        };
+        match (range, zone) {
+            (Some((first, last)), Some(zone)) if first <= last && last - first < MAX_SEATS_PER_REQUEST as u32 => {
+                seats.extend((first..=last).map(|number| (zone.to_string(), number)));
+            }
+            (Some(_), Some(_)) => return Err(TicketingError::InvalidArgument(format!("Invalid seat range: {}", arg))),
+            (Some(_), None) => {
+                return Err(TicketingError::InvalidArgument(format!("Seat {} has no zone, write the zone first.", arg)))
+            }
+            (None, _) => zone = Some(arg),
         }
     }
+    if seats.is_empty() {
+        return Err(TicketingError::InvalidArgument(
+            "Usage: pick <VIP|General> <zone> <number|first-last>... [<zone> <number>...]".to_string(),
+        ));
+    }
+    Ok(seats)
 }
 
-fn handle_client(mut stream: TcpStream, seating_structure: Arc<Mutex<SeatingStructure>>) {
+fn format_seats(seats: &[(String, u32)]) -> String {
+    // Misma forma que la respuesta de `find`: (ZonaA,2) (ZonaA,3)
+    seats
+        .iter()
+        .map(|(zone, number)| format!("({},{})", zone, number))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Comandos del protocolo TCP, cualquier otro se cuenta como `unknown` en las métricas
+const COMMANDS: [&str; 16] = [
+    "find", "reserve", "purchase", "release", "refund", "ticket", "pick", "waitlist", "claim", "subscribe", "unsubscribe",
+    "map", "stats", "availability", "ping", "health",
+];
+
+fn outcome(response: &str) -> &str {
+    // Resultado de una petición para el registro: `ok` o el código de `TicketingError`
+    response
+        .strip_prefix("ERR ")
+        .and_then(|error| error.split_whitespace().next())
+        .unwrap_or("ok")
+}
+
+fn handle_client(
+    mut stream: TcpStream,
+    id: u64,
+    seating_structure: Arc<RwLock<SeatingStructure>>,
+    shutdown: Arc<AtomicBool>,
+) {
     /**
     Maneja la comunicaion con un el cliente,
     procesando solicitudes de búsqueda, reserva y compra de los asientos
     */
-    let mut buffer = [0; 512];
+    let _holds = ConnectionHolds { id, seating_structure: Arc::clone(&seating_structure) };
+    let peer = stream.peer_addr().map(|peer| peer.to_string()).unwrap_or_default();
+    logging::info("Connection opened", json!({ "conn": id, "peer": peer }));
+    metrics::connection_opened("tcp");
+    let mut reader = match stream.try_clone() {
+        Ok(reader) => BufReader::new(reader),
+        Err(e) => {
+            logging::warn("Failed to open connection", json!({ "conn": id, "error": e.to_string() }));
+            metrics::connection_closed("tcp");
+            return;
+        }
+    };
+    // Petición en curso, puede llegar en varias lecturas
+    let mut line = String::new();
+    // Eventos de asientos, solo después de `subscribe`
+    let mut events: Option<Receiver<notify::Event>> = None;
     loop {
-        match stream.read(&mut buffer) {
-            Ok(size) => {
-                let request = String::from_utf8_lossy(&buffer[..size]);
-                //println!("Received request: {}", request);
-                //println!("{:?}", request);
+        if let Some(events) = &events {
+            let pending: String = events.try_iter().map(|event| event.line()).collect();
+            if let Err(e) = stream.write_all(pending.as_bytes()) {
+                logging::warn("Failed to write to stream", json!({ "conn": id, "error": e.to_string() }));
+                break;
+            }
+        }
+        // Una petición por línea; si el tiempo de espera la corta, se completa en la siguiente vuelta
+        let limit = (MAX_REQUEST_LINE + 1 - line.len()) as u64;
+        match reader.by_ref().take(limit).read_line(&mut line) {
+            Ok(0) => {
+                // Lectura de 0 bytes: el cliente cerró la conexión
+                logging::info("Client disconnected", json!({ "conn": id }));
+                break;
+            }
+            Ok(_) if !line.ends_with('\n') && line.len() > MAX_REQUEST_LINE => {
+                let error = TicketingError::MalformedRequest(format!("Request is longer than {} bytes.", MAX_REQUEST_LINE));
+                if let Err(e) = stream.write_all(format!("{}\n", error.reply()).as_bytes()) {
+                    logging::warn("Failed to write to stream", json!({ "conn": id, "error": e.to_string() }));
+                }
+                logging::warn("Request too long, closing connection", json!({ "conn": id }));
+                break;
+            }
+            // El cliente cerró a mitad de una línea, la siguiente lectura lo detecta
+            Ok(_) if !line.ends_with('\n') => {}
+            Ok(_) => {
+                let request = std::mem::take(&mut line);
+                if request.trim().is_empty() {
+                    continue;
+                }
+                let request_id = logging::next_request_id();
+                let started = Instant::now();
+                logging::debug("Received request", json!({ "conn": id, "req": request_id, "request": request.trim() }));
                 let mut parts = request.split_whitespace();
                 let command = parts.next().unwrap_or("");
                 let typ = parts.next().unwrap_or("");
                 let seat_count = parts.next().unwrap_or("").parse::<u32>().unwrap_or(0);
 
-                let response = match command {
+                // Cada comando responde su resultado o `ERR <CODIGO> <mensaje>`, la conexión sigue abierta
+                let result: Result<String, TicketingError> = match command {
                     "find" => {
-                        let seating_structure = seating_structure.lock().unwrap();
-                        let seat_combination = seating_structure.find_free_seats(typ, seat_count);
-                        if seat_combination.is_empty() {
-                            "No suitable seats found.".to_string()
-                        } else {
-                            let mut response = seat_combination
-                                .iter()
-                                .map(|(zone, number)| format!("({},{})", zone, number))
-                                .collect::<Vec<String>>()
-                                .join(" ");
-                            response
-                        }
+                        // Las búsquedas solo leen, pueden correr en paralelo
+                        // find VIP 4 [same-zone] [together] [min-vision=90] [zones=ZonaA,ZonaB] [exclude=ZonaC]
+                        // [wheelchair=1] [aisle] [near-exit] [unrestricted]
+                        let seating_structure = read_seats(&seating_structure);
+                        let searching = Instant::now();
+                        let result = match FindConstraints::from_args(request.split_whitespace().skip(3)) {
+                            _ if !seating_structure.sales_open(typ) => Err(TicketingError::SalesClosed(typ.to_string())),
+                            Err(e) => Err(e),
+                            Ok(constraints) => match seating_structure.find_free_seats(typ, seat_count, &constraints) {
+                                Ok(seat_combination) => Ok(format_seats(&seat_combination)),
+                                Err(TicketingError::NoSeatsFound) => {
+                                    // Sin asientos: el error, las alternativas ordenadas, la lista de espera y `END_MARKER`
+                                    let alternatives = seating_structure.suggest_alternatives(typ, seat_count, &constraints);
+                                    let mut response = format!("{}\n", TicketingError::NoSeatsFound.reply());
+                                    if !alternatives.is_empty() {
+                                        response.push_str("Alternatives:\n");
+                                    }
+                                    for (rank, alternative) in alternatives.iter().enumerate() {
+                                        response.push_str(&alternative.line(rank + 1));
+                                    }
+                                    response.push_str(&format!("Join the waitlist with: waitlist join {} {}\n", typ, seat_count));
+                                    response.push_str(END_MARKER);
+                                    response.push('\n');
+                                    Ok(response)
+                                }
+                                Err(e) => Err(e),
+                            },
+                        };
+                        metrics::find_duration(searching.elapsed());
+                        result
                     }
                     "reserve" => {
                         // reserve zona numero
@@ -477,28 +1429,16 @@ fn handle_client(mut stream: TcpStream, seating_structure: Arc<Mutex<SeatingStru
                         let typ = parts.next().unwrap_or("");
                         let zonesend = parts.next().unwrap_or("");
                         let seat_number = parts.next().unwrap_or("").parse::<u32>().unwrap_or(0);
-                        let mut seating_structure = seating_structure.lock().unwrap();
-                        let categories = &mut seating_structure.categories;
-                        for category in categories {
-                            for zone in &mut category.zones {
-                                let seats = match typ {
-                                    "VIP" => &mut zone.Vip.seats,
-                                    "General" => &mut zone.General.seats,
-                                    _ => {
-                                        eprintln!("Invalid category: {}", typ);
-                                        return;
-                                    }
-                                };
-                                for seat in seats {
-                                    if zone.name == zonesend && seat.number == seat_number {
-                                        seat.status = SeatStatus::Reserved;
-                                    }
-                                }
-                            }
+                        let mut seating_structure = write_seats(&seating_structure);
+                        if typ != "VIP" && typ != "General" {
+                            Err(TicketingError::InvalidCategory(typ.to_string()))
+                        } else {
+                            // Solo asientos libres o ya reservados por esta conexión, nunca pisa otra reserva o compra
+                            seating_structure
+                                .hold_seat(typ, zonesend, seat_number, id)
+                                //Mandar de regreso la misma información que llego
+                                .map(|()| format!("purchase {} {} {}", typ, zonesend, seat_number))
                         }
-                        //Mandar de regreso la misma información que llego
-                        format!("purchase {} {} {}", typ, zonesend, seat_number)
-
                     }
                     "purchase" => {
                         // purchase zona numero
@@ -509,38 +1449,353 @@ fn handle_client(mut stream: TcpStream, seating_structure: Arc<Mutex<SeatingStru
                         let typ = parts.next().unwrap_or("");
                         let zonesend = parts.next().unwrap_or("");
                         let seat_number = parts.next().unwrap_or("").parse::<u32>().unwrap_or(0);
-                        let mut seating_structure = seating_structure.lock().unwrap();
-                        let categories = &mut seating_structure.categories;
-                        for category in categories {
-                            for zone in &mut category.zones {
-                                let seats = match typ {
-                                    "VIP" => &mut zone.Vip.seats,
-                                    "General" => &mut zone.General.seats,
-                                    _ => {
-                                        eprintln!("Invalid category: {}", typ);
-                                        return;
-                                    }
-                                };
-                                for seat in seats {
-                                    if zone.name == zonesend && seat.number == seat_number {
-                                        seat.status = SeatStatus::Purchased;
-                                    }
+                        let mut seating_structure = write_seats(&seating_structure);
+                        if !seating_structure.sales_open(typ) {
+                            Err(TicketingError::SalesClosed(typ.to_string()))
+                        } else {
+                            seating_structure.purchase_seat(typ, zonesend, seat_number, id).map(|order_id| {
+                                let orders = &seating_structure.orders;
+                                let code = orders.order(order_id).map(|order| orders.ticket_code(order)).unwrap_or_default();
+                                format!("Purchase completed (order {}) ticket {}", order_id, code)
+                            })
+                        }
+                    }
+                    "release" => {
+                        // release VIP ZonaA 2: devuelve un asiento reservado por esta conexión
+                        // release all: devuelve todas las reservas de esta conexión
+                        let mut parts = request.split_whitespace().skip(2);
+                        let zonesend = parts.next().unwrap_or("");
+                        let seat_number = parts.next().unwrap_or("").parse::<u32>().unwrap_or(0);
+                        let mut seating_structure = write_seats(&seating_structure);
+                        if typ == "all" {
+                            let released = seating_structure.release_holds(id);
+                            Ok(format!("Released {} seats", released))
+                        } else {
+                            seating_structure
+                                .release_seat(typ, zonesend, seat_number, id)
+                                .map(|()| format!("Released {} {} {}", typ, zonesend, seat_number))
+                        }
+                    }
+                    "refund" => {
+                        // refund order 12: reembolsa una orden de esta conexión
+                        // refund VIP ZonaA 2: reembolsa la compra vigente de un asiento
+                        let mut parts = request.split_whitespace().skip(1);
+                        let mut seating_structure = write_seats(&seating_structure);
+                        match (parts.next(), parts.next(), parts.next()) {
+                            (Some("order"), Some(order_id), None) => match order_id.parse::<u64>() {
+                                Ok(order_id) => seating_structure
+                                    .refund_order(order_id, id)
+                                    .map(|amount| format!("Refunded order {}: {}", order_id, amount)),
+                                Err(_) => Err(TicketingError::InvalidArgument(format!("Invalid order number: {}", order_id))),
+                            },
+                            (Some(typ), Some(zonesend), Some(seat_number)) => {
+                                let seat_number = seat_number.parse::<u32>().unwrap_or(0);
+                                seating_structure
+                                    .refund_seat(typ, zonesend, seat_number, id)
+                                    .map(|(order_id, amount)| format!("Refunded order {}: {}", order_id, amount))
+                            }
+                            _ => Err(TicketingError::InvalidArgument(
+                                "Usage: refund order <id> | refund <VIP|General> <zone> <number>".to_string(),
+                            )),
+                        }
+                    }
+                    "ticket" => {
+                        // ticket 12: muestra el boleto de una orden de esta conexión
+                        let seating_structure = read_seats(&seating_structure);
+                        let orders = &seating_structure.orders;
+                        match typ.parse::<u64>() {
+                            Err(_) => Err(TicketingError::InvalidArgument(format!("Invalid order number: {}", typ))),
+                            Ok(order_id) => match orders.order(order_id) {
+                                Some(order) if order.holder == Some(id) => Ok(format!(
+                                    "Ticket {} event={} zone={} seat={} category={} price={}{} code={}",
+                                    order.ticket_id,
+                                    orders.event,
+                                    order.zone,
+                                    order.number,
+                                    order.typ,
+                                    order.price,
+                                    if order.refund.is_some() { " (refunded)" } else { "" },
+                                    orders.ticket_code(order)
+                                )),
+                                Some(_) => Err(TicketingError::OrderNotOwned(order_id)),
+                                None => Err(TicketingError::OrderNotFound(order_id)),
+                            },
+                        }
+                    }
+                    "pick" => {
+                        // pick VIP ZonaB 2 3 ZonaC 1-2: reserva exactamente esos asientos, todos o ninguno
+                        parse_seat_list(request.split_whitespace().skip(2)).and_then(|seats| {
+                            write_seats(&seating_structure)
+                                .hold_seats(typ, &seats, id)
+                                .map(|()| format!("Held {} {}", typ, format_seats(&seats)))
+                        })
+                    }
+                    "waitlist" => {
+                        // waitlist join VIP 4: espera asientos de un tipo agotado
+                        // waitlist leave: sale de la fila
+                        // waitlist: muestra la oferta pendiente o la posición en la fila
+                        let mut seating_structure = write_seats(&seating_structure);
+                        match typ {
+                            "join" => {
+                                let mut parts = request.split_whitespace().skip(2);
+                                let typ = parts.next().unwrap_or("");
+                                let seat_count = parts.next().unwrap_or("").parse::<u32>().unwrap_or(0);
+                                seating_structure
+                                    .join_waitlist(id, typ, seat_count)
+                                    .map(|()| waitlist::format_status(&seating_structure, id, Instant::now()))
+                            }
+                            "leave" => {
+                                if seating_structure.leave_waitlist(id) {
+                                    Ok("Left the waitlist.".to_string())
+                                } else {
+                                    Err(TicketingError::NotOnWaitlist)
                                 }
                             }
+                            "" | "status" => Ok(waitlist::format_status(&seating_structure, id, Instant::now())),
+                            _ => Err(TicketingError::InvalidArgument(
+                                "Usage: waitlist [join <VIP|General> <count> | leave | status]".to_string(),
+                            )),
                         }
-                        "Purchase completed".to_string()
                     }
-                    _ => "Invalid command.".to_string(),
+                    "claim" => {
+                        // claim: compra los asientos ofrecidos por la lista de espera
+                        write_seats(&seating_structure).claim_offer(id).map(|order_ids| {
+                            format!(
+                                "Claimed {} seats (orders {}). Use ticket <order> to get each ticket.",
+                                order_ids.len(),
+                                order_ids.iter().map(|order_id| order_id.to_string()).collect::<Vec<String>>().join(", ")
+                            )
+                        })
+                    }
+                    "subscribe" => {
+                        // subscribe [VIP|General] [zona...]: recibe los cambios de asientos
+                        // como líneas `EVENT ...`, también los vencimientos y ofertas de la lista de espera
+                        let mut seating_structure = write_seats(&seating_structure);
+                        match notify::Filter::from_args(request.split_whitespace().skip(1), &seating_structure) {
+                            Ok(filter) => {
+                                let scope = filter.describe();
+                                events = Some(seating_structure.notifier.subscribe(id, filter));
+                                Ok(format!("Subscribed to {}.\n", scope))
+                            }
+                            // Con salto de línea igual que la confirmación, el cliente lee por líneas
+                            Err(e) => Ok(format!("{}\n", TicketingError::InvalidArgument(e).reply())),
+                        }
+                    }
+                    "unsubscribe" => {
+                        events = None;
+                        if write_seats(&seating_structure).notifier.unsubscribe(id) {
+                            Ok("Unsubscribed.".to_string())
+                        } else {
+                            Err(TicketingError::NotSubscribed)
+                        }
+                    }
+                    "map" => {
+                        // map [vision] [plain] [features]: dibuja el mapa de asientos
+                        MapOptions::from_args(request.split_whitespace().skip(1))
+                            .map(|options| render::render_seat_map(&read_seats(&seating_structure), options))
+                            .map_err(TicketingError::InvalidArgument)
+                    }
+                    "stats" | "availability" => {
+                        // stats [tipo] [zona]: conteo de asientos por estado
+                        let filters: Vec<&str> = request.split_whitespace().skip(1).collect();
+                        stats::format_stats(&read_seats(&seating_structure), &filters)
+                    }
+                    // Chequeos del balanceador: `ping` solo confirma que el servidor responde
+                    "ping" => Ok("PONG".to_string()),
+                    "health" => Ok(health::check(&read_seats(&seating_structure)).format()),
+                    _ => Err(TicketingError::InvalidCommand(command.to_string())),
                 };
+                let mut response = result.unwrap_or_else(|e| e.reply());
+                // Toda respuesta termina en salto de línea, las de varias líneas con `END_MARKER`
+                if !response.ends_with('\n') {
+                    response.push('\n');
+                }
+                logging::info(
+                    "Request",
+                    json!({
+                        "conn": id,
+                        "req": request_id,
+                        "command": command,
+                        "outcome": outcome(&response),
+                        "duration_us": started.elapsed().as_micros() as u64,
+                    }),
+                );
+                let known = COMMANDS.iter().find(|known| **known == command).copied();
+                metrics::command("tcp", known.unwrap_or("unknown"), outcome(&response));
 
-                if let Err(e) = stream.write(response.as_bytes()) {
-                    eprintln!("Failed to write to stream: {}", e);
+                if let Err(e) = stream.write_all(response.as_bytes()) {
+                    logging::warn("Failed to write to stream", json!({ "conn": id, "req": request_id, "error": e.to_string() }));
                     break;
                 }
             }
-            _ => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                // No llegó ningún comando, se revisa si el servidor se está cerrando
+                if shutdown.load(Ordering::SeqCst) {
+                    if let Err(e) = stream.write_all(format!("{}\n", TicketingError::ShuttingDown.reply()).as_bytes()) {
+                        logging::warn("Failed to write to stream", json!({ "conn": id, "error": e.to_string() }));
+                    }
+                    logging::info("Connection closed by server shutdown", json!({ "conn": id }));
+                    break;
+                }
+            }
+            Err(e) => {
+                logging::warn("Failed to read from stream", json!({ "conn": id, "error": e.to_string() }));
+                break;
+            }
         }
     }
+    metrics::connection_closed("tcp");
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seat_ref(typ: &str, zone: &str, number: u32) -> SeatRef {
+        SeatRef::new(typ, zone, number)
+    }
+
+    #[test]
+    fn hold_seat_only_takes_free_seats_or_own_holds() {
+        let mut seating_structure = SeatingStructure::new();
+        assert_eq!(seating_structure.hold_seat("VIP", "ZonaB", 2, 1), Ok(()));
+        // La misma conexión puede repetir la reserva, otra no la pisa
+        assert_eq!(seating_structure.hold_seat("VIP", "ZonaB", 2, 1), Ok(()));
+        assert_eq!(seating_structure.hold_seat("VIP", "ZonaB", 2, 2), Err(TicketingError::SeatNotFree(seat_ref("VIP", "ZonaB", 2))));
+        assert_eq!(seating_structure.seat("VIP", "ZonaB", 2).and_then(|seat| seat.holder), Some(1));
+
+        assert_eq!(seating_structure.hold_seat("VIP", "ZonaA", 1, 1), Err(TicketingError::SeatNotFree(seat_ref("VIP", "ZonaA", 1))));
+        seating_structure.seat_mut("VIP", "ZonaC", 1).unwrap().status = SeatStatus::Blocked;
+        assert_eq!(seating_structure.hold_seat("VIP", "ZonaC", 1, 1), Err(TicketingError::SeatNotFree(seat_ref("VIP", "ZonaC", 1))));
+        assert_eq!(seating_structure.hold_seat("VIP", "ZonaZ", 1, 1), Err(TicketingError::SeatNotFound(seat_ref("VIP", "ZonaZ", 1))));
+    }
+
+    #[test]
+    fn release_only_frees_own_holds() {
+        let mut seating_structure = SeatingStructure::new();
+        seating_structure.hold_seat("VIP", "ZonaB", 1, 1).unwrap();
+        seating_structure.hold_seat("VIP", "ZonaB", 2, 1).unwrap();
+        seating_structure.hold_seat("VIP", "ZonaB", 3, 2).unwrap();
+        assert_eq!(seating_structure.release_seat("VIP", "ZonaB", 1, 2), Err(TicketingError::SeatNotHeld(seat_ref("VIP", "ZonaB", 1))));
+        assert_eq!(seating_structure.release_seat("VIP", "ZonaB", 4, 1), Err(TicketingError::SeatNotHeld(seat_ref("VIP", "ZonaB", 4))));
+        assert_eq!(seating_structure.release_seat("VIP", "ZonaB", 1, 1), Ok(()));
+        assert_eq!(seating_structure.seat("VIP", "ZonaB", 1).map(|seat| seat.status.clone()), Some(SeatStatus::Free));
+
+        // Al desconectarse solo se liberan las reservas de esa conexión
+        assert_eq!(seating_structure.release_holds(1), 1);
+        assert_eq!(seating_structure.seat("VIP", "ZonaB", 3).and_then(|seat| seat.holder), Some(2));
+    }
+
+    #[test]
+    fn wheelchair_space_needs_its_companion_first() {
+        let mut seating_structure = SeatingStructure::new();
+        assert_eq!(
+            seating_structure.hold_seat("General", "ZonaB", 1, 1),
+            Err(TicketingError::CompanionRequired(seat_ref("General", "ZonaB", 1)))
+        );
+        assert_eq!(seating_structure.hold_seat("General", "ZonaB", 2, 1), Ok(()));
+        assert_eq!(seating_structure.hold_seat("General", "ZonaB", 1, 1), Ok(()));
+    }
+
+    #[test]
+    fn wheelchair_space_is_not_sold_after_releasing_its_companion() {
+        let mut seating_structure = SeatingStructure::new();
+        seating_structure.hold_seat("General", "ZonaB", 2, 1).unwrap();
+        seating_structure.hold_seat("General", "ZonaB", 1, 1).unwrap();
+        seating_structure.release_seat("General", "ZonaB", 2, 1).unwrap();
+        assert_eq!(
+            seating_structure.purchase_seat("General", "ZonaB", 1, 1),
+            Err(TicketingError::CompanionRequired(seat_ref("General", "ZonaB", 1)))
+        );
+
+        // Con el acompañante comprado primero, el espacio se vende
+        seating_structure.purchase_seat("General", "ZonaB", 2, 1).unwrap();
+        assert!(seating_structure.purchase_seat("General", "ZonaB", 1, 1).is_ok());
+    }
 
+    #[test]
+    fn hold_seats_rejects_unknown_seats_before_checking_companions() {
+        let mut seating_structure = SeatingStructure::new();
+        let seats = vec![("ZonaB".to_string(), 1), ("ZonaB".to_string(), u32::MAX)];
+        let failure = SeatFailure { zone: "ZonaB".to_string(), number: u32::MAX, reason: "not found".to_string() };
+        assert_eq!(seating_structure.hold_seats("General", &seats, 1), Err(TicketingError::SeatsUnavailable(vec![failure])));
+
+        let seats = vec![("ZonaB".to_string(), 1), ("ZonaB".to_string(), 2)];
+        assert_eq!(seating_structure.hold_seats("General", &seats, 1), Ok(()));
+    }
+
+    #[test]
+    fn purchase_seat_accepts_free_seats_and_own_holds() {
+        let mut seating_structure = SeatingStructure::new();
+        assert_eq!(seating_structure.purchase_seat("VIP", "ZonaB", 1, 1), Ok(1));
+        seating_structure.hold_seat("VIP", "ZonaB", 2, 1).unwrap();
+        assert_eq!(seating_structure.purchase_seat("VIP", "ZonaB", 2, 1), Ok(2));
+
+        let seat = seating_structure.seat("VIP", "ZonaB", 2).unwrap();
+        assert_eq!((seat.status.clone(), seat.holder), (SeatStatus::Purchased, Some(1)));
+        assert_eq!(seating_structure.orders.order(2).map(|order| order.price), Some(120));
+    }
+
+    #[test]
+    fn purchase_seat_rejects_held_sold_and_blocked_seats() {
+        let mut seating_structure = SeatingStructure::new();
+        seating_structure.hold_seat("VIP", "ZonaB", 3, 1).unwrap();
+        assert_eq!(seating_structure.purchase_seat("VIP", "ZonaB", 3, 2), Err(TicketingError::SeatNotFree(seat_ref("VIP", "ZonaB", 3))));
+
+        seating_structure.purchase_seat("VIP", "ZonaB", 1, 1).unwrap();
+        assert_eq!(seating_structure.purchase_seat("VIP", "ZonaB", 1, 2), Err(TicketingError::SeatNotFree(seat_ref("VIP", "ZonaB", 1))));
+
+        seating_structure.seat_mut("VIP", "ZonaC", 1).unwrap().status = SeatStatus::Blocked;
+        assert_eq!(seating_structure.purchase_seat("VIP", "ZonaC", 1, 1), Err(TicketingError::SeatNotFree(seat_ref("VIP", "ZonaC", 1))));
+        assert_eq!(seating_structure.purchase_seat("Palco", "ZonaC", 1, 1), Err(TicketingError::InvalidCategory("Palco".to_string())));
+        // Solo la primera compra dejó una orden
+        assert_eq!(seating_structure.orders.orders.len(), 1);
+    }
+
+    #[test]
+    fn operator_release_refunds_the_sold_order() {
+        let mut seating_structure = SeatingStructure::new();
+        let order_id = seating_structure.purchase_seat("VIP", "ZonaB", 1, 1).unwrap();
+        assert_eq!(seating_structure.operator_release("VIP", "ZonaB", 1), Ok(Some((order_id, 120))));
+        assert_eq!(seating_structure.seat("VIP", "ZonaB", 1).map(|seat| seat.status.clone()), Some(SeatStatus::Free));
+        assert_eq!(seating_structure.orders.seat_order_id("VIP", "ZonaB", 1), None);
+        // Un asiento reservado se libera sin reembolso
+        seating_structure.hold_seat("VIP", "ZonaB", 2, 1).unwrap();
+        assert_eq!(seating_structure.operator_release("VIP", "ZonaB", 2), Ok(None));
+    }
+
+    #[test]
+    fn hold_seat_respects_closed_sales() {
+        let mut seating_structure = SeatingStructure::new();
+        seating_structure.closed_sales.push("VIP".to_string());
+        assert_eq!(seating_structure.hold_seat("VIP", "ZonaB", 2, 1), Err(TicketingError::SalesClosed("VIP".to_string())));
+        assert_eq!(seating_structure.hold_seat("General", "ZonaC", 1, 1), Ok(()));
+    }
+
+    #[test]
+    fn hold_seats_is_all_or_nothing() {
+        let mut seating_structure = SeatingStructure::new();
+        let seats = vec![("ZonaB".to_string(), 1), ("ZonaA".to_string(), 1)];
+        let failure = SeatFailure { zone: "ZonaA".to_string(), number: 1, reason: "purchased".to_string() };
+        assert_eq!(seating_structure.hold_seats("VIP", &seats, 1), Err(TicketingError::SeatsUnavailable(vec![failure])));
+        assert_eq!(seating_structure.seat("VIP", "ZonaB", 1).map(|seat| seat.status.clone()), Some(SeatStatus::Free));
+    }
+
+    #[test]
+    fn parse_seat_list_reads_zones_numbers_and_ranges() {
+        let seats = parse_seat_list("ZonaB 2 3 ZonaC 1-2".split_whitespace());
+        let expected = [("ZonaB", 2), ("ZonaB", 3), ("ZonaC", 1), ("ZonaC", 2)];
+        assert_eq!(seats, Ok(expected.iter().map(|(zone, number)| (zone.to_string(), *number)).collect()));
+    }
+
+    #[test]
+    fn parse_seat_list_rejects_bad_lists() {
+        for list in ["2 ZonaB", "ZonaB 3-1", "ZonaB 1-9", "ZonaB 1-", "ZonaB", ""] {
+            assert!(
+                matches!(parse_seat_list(list.split_whitespace()), Err(TicketingError::InvalidArgument(_))),
+                "{}",
+                list
+            );
+        }
+    }
+}
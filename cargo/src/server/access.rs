@@ -0,0 +1,107 @@
+use super::error::{SeatRef, TicketingError};
+use super::{SeatStatus, SeatingStructure};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SeatFeatures {
+    /**
+    Atributos de accesibilidad de un asiento:
+    - `wheelchair`: Espacio para silla de ruedas, solo se vende junto a un asiento acompañante contiguo.
+    - `companion`: Asiento para el acompañante de un espacio para silla de ruedas.
+    - `aisle`: Asiento de pasillo.
+    - `restricted_view`: Asiento con la vista parcialmente tapada.
+    - `near_exit`: Asiento cerca de una salida.
+    */
+    pub wheelchair: bool,
+    pub companion: bool,
+    pub aisle: bool,
+    pub restricted_view: bool,
+    pub near_exit: bool,
+}
+
+impl SeatFeatures {
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.wheelchair, "wheelchair"),
+            (self.companion, "companion"),
+            (self.aisle, "aisle"),
+            (self.restricted_view, "restricted-view"),
+            (self.near_exit, "near-exit"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+
+    pub fn format(&self) -> String {
+        // Lista separada por comas, `-` si el asiento no tiene atributos
+        let names = self.names();
+        if names.is_empty() {
+            "-".to_string()
+        } else {
+            names.join(",")
+        }
+    }
+
+    pub fn parse(list: &str) -> Result<Self, String> {
+        // Inverso de `format`
+        let mut features = SeatFeatures::default();
+        if list == "-" {
+            return Ok(features);
+        }
+        for name in list.split(',') {
+            match name {
+                "wheelchair" => features.wheelchair = true,
+                "companion" => features.companion = true,
+                "aisle" => features.aisle = true,
+                "restricted-view" => features.restricted_view = true,
+                "near-exit" => features.near_exit = true,
+                _ => return Err(format!("Invalid seat feature: {}", name)),
+            }
+        }
+        Ok(features)
+    }
+}
+
+impl SeatingStructure {
+    pub fn is_wheelchair_space(&self, typ: &str, zone_name: &str, number: u32) -> bool {
+        self.seat(typ, zone_name, number).is_some_and(|seat| seat.features.wheelchair)
+    }
+
+    pub fn companion_held(&self, typ: &str, zone_name: &str, number: u32, holder: u64) -> bool {
+        /**
+        Revisa si la conexión ya tiene (reservado o comprado) un asiento acompañante
+        contiguo al espacio para silla de ruedas indicado.
+        */
+        [number.checked_sub(1), number.checked_add(1)].into_iter().flatten().any(|neighbour| {
+            self.seat(typ, zone_name, neighbour).is_some_and(|seat| {
+                seat.features.companion
+                    && seat.holder == Some(holder)
+                    && (seat.status == SeatStatus::Reserved || seat.status == SeatStatus::Purchased)
+            })
+        })
+    }
+
+    pub fn missing_companion(&self, typ: &str, zone_name: &str, number: u32, holder: u64, selection: &[(String, u32)]) -> bool {
+        /**
+        Para una selección de `hold_seats`: true si el asiento es un espacio para silla
+        de ruedas sin acompañante contiguo, ni en la selección ni ya reservado por la conexión.
+        */
+        if !self.is_wheelchair_space(typ, zone_name, number) {
+            return false;
+        }
+        let companion_selected = selection.iter().any(|(zone, neighbour)| {
+            zone == zone_name
+                && neighbour.abs_diff(number) == 1
+                && self.seat(typ, zone, *neighbour).is_some_and(|seat| seat.features.companion)
+        });
+        !companion_selected && !self.companion_held(typ, zone_name, number, holder)
+    }
+
+    pub fn check_wheelchair_booking(&self, typ: &str, zone_name: &str, number: u32, holder: u64) -> Result<(), TicketingError> {
+        // Un espacio para silla de ruedas solo se reserva o compra con su acompañante contiguo
+        if self.is_wheelchair_space(typ, zone_name, number) && !self.companion_held(typ, zone_name, number, holder) {
+            return Err(TicketingError::CompanionRequired(SeatRef::new(typ, zone_name, number)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,368 @@
+use super::error::{SeatRef, TicketingError};
+use super::logging;
+use super::pool::ConnectionSlot;
+use super::render::{self, MapOptions};
+use super::{orders, read_seats, stats, storage, write_seats, Seat, SeatStatus, SeatingStructure, ServerConfig, SHUTDOWN_POLL_INTERVAL};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Operadores conectados a la vez, el canal no necesita más
+const MAX_CONNECTIONS: usize = 4;
+
+// Tiempo para enviar `auth <clave>`, una conexión que no se autentica no ocupa un lugar
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const HELP: &str = "Admin commands:
+  dump
+  map [vision] [plain] [features]
+  stats [VIP|General] [zone]
+  validate <ticket code>
+  attendance
+  release <VIP|General> <zone> <number>    (refunds the order of a sold seat)
+  block <VIP|General> <zone> <number>
+  vision <VIP|General> <zone> <number> <percentage>
+  price <VIP|General> <zone> <number> <price>
+  open <VIP|General>
+  close <VIP|General>
+  reload
+  shutdown
+";
+
+pub fn start_admin(
+    config: ServerConfig,
+    address: String,
+    seating_structure: Arc<RwLock<SeatingStructure>>,
+    shutdown: Arc<AtomicBool>,
+) -> io::Result<()> {
+    /**
+    Inicia el canal de administración en su propio hilo.
+    Cada operador conectado se atiende en un hilo aparte, los comandos son
+    de una línea y cada acción queda registrada en la consola del servidor.
+    Con `MAX_CONNECTIONS` operadores conectados, los siguientes reciben `SERVER_BUSY`.
+    */
+    // Sin clave cualquier proceso local podría cerrar el servidor o cambiar precios
+    if config.admin_token.is_none() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "admin channel needs an admin token"));
+    }
+    let listener = TcpListener::bind(&address)?;
+    listener.set_nonblocking(true)?;
+    logging::info("Admin channel is listening", json!({ "address": address }));
+
+    let active_connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        while !shutdown.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((mut stream, peer)) => {
+                    let slot = match ConnectionSlot::acquire(&active_connections, MAX_CONNECTIONS) {
+                        Some(slot) => slot,
+                        None => {
+                            logging::warn("Admin channel busy, connection rejected", json!({ "peer": peer.to_string() }));
+                            let _ = stream.write_all(format!("{}\n", TicketingError::ServerBusy.reply()).as_bytes());
+                            continue;
+                        }
+                    };
+                    let config = config.clone();
+                    let seating_structure = Arc::clone(&seating_structure);
+                    let shutdown = Arc::clone(&shutdown);
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        if let Err(e) = handle_admin(stream, peer, &config, &seating_structure, &shutdown) {
+                            logging::warn("Admin connection error", json!({ "peer": peer.to_string(), "error": e.to_string() }));
+                        }
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(SHUTDOWN_POLL_INTERVAL),
+                Err(e) => logging::warn("Failed to accept admin connection", json!({ "error": e.to_string() })),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle_admin(
+    stream: TcpStream,
+    peer: SocketAddr,
+    config: &ServerConfig,
+    seating_structure: &RwLock<SeatingStructure>,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    /**
+    Atiende a un operador. La primera línea debe ser `auth <token>`;
+    sin `admin_token` configurado nadie se autentica.
+    */
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(AUTH_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+    logging::info("Admin connected", json!({ "peer": peer.to_string() }));
+
+    // Sin la línea a tiempo cuenta como clave incorrecta
+    let line = lines.next().and_then(Result::ok).unwrap_or_default();
+    let authenticated = match (config.admin_token.as_deref(), line.trim().strip_prefix("auth ")) {
+        (Some(expected), Some(token)) => token_matches(expected, token),
+        _ => false,
+    };
+    if !authenticated {
+        logging::warn("Admin authentication failed", json!({ "peer": peer.to_string() }));
+        writer.write_all(format!("{}\n", TicketingError::AuthenticationFailed.reply()).as_bytes())?;
+        return Ok(());
+    }
+    // Un operador autenticado puede quedarse conectado sin enviar comandos
+    writer.set_read_timeout(None)?;
+    writer.write_all(b"Authenticated.\n")?;
+
+    for line in lines {
+        let line = line?;
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+
+        let request_id = logging::next_request_id();
+        let started = Instant::now();
+        let response = execute(command, config, seating_structure, shutdown);
+        // Toda acción del operador queda registrada
+        logging::info(
+            "Admin command",
+            json!({
+                "peer": peer.to_string(),
+                "req": request_id,
+                "command": command,
+                "response": response.lines().next().unwrap_or(""),
+                "duration_us": started.elapsed().as_micros() as u64,
+            }),
+        );
+        writer.write_all(response.as_bytes())?;
+        if !response.ends_with('\n') {
+            writer.write_all(b"\n")?;
+        }
+        if command == "shutdown" {
+            break;
+        }
+    }
+
+    logging::info("Admin disconnected", json!({ "peer": peer.to_string() }));
+    Ok(())
+}
+
+fn token_matches(expected: &str, token: &str) -> bool {
+    /**
+    Compara la clave recibida con la configurada en tiempo constante:
+    se firman las dos con HMAC y `verify_slice` compara las firmas sin cortar en el primer byte distinto,
+    así el tiempo de respuesta no revela cuántos caracteres acertó.
+    */
+    let sign = |value: &str| {
+        let mut mac = HmacSha256::new_from_slice(expected.as_bytes()).expect("HMAC accepts keys of any size");
+        mac.update(value.as_bytes());
+        mac
+    };
+    let expected_signature = sign(expected).finalize().into_bytes();
+    sign(token).verify_slice(&expected_signature).is_ok()
+}
+
+fn execute(
+    command: &str,
+    config: &ServerConfig,
+    seating_structure: &RwLock<SeatingStructure>,
+    shutdown: &AtomicBool,
+) -> String {
+    /**
+    Ejecuta un comando de administración y retorna la respuesta para el operador.
+    Las fallas se responden como en TCP, `ERR <CODIGO> <mensaje>`.
+    */
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    match parts.as_slice() {
+        ["help"] => HELP.to_string(),
+        ["dump"] => storage::format_state(&read_seats(seating_structure)),
+        ["map", options @ ..] => match MapOptions::from_args(options.iter().copied()) {
+            Ok(options) => render::render_seat_map(&read_seats(seating_structure), options),
+            Err(e) => TicketingError::InvalidArgument(e).reply(),
+        },
+        ["stats", filters @ ..] => {
+            match stats::format_stats(&read_seats(seating_structure), filters) {
+                Ok(stats) => stats,
+                Err(e) => e.reply(),
+            }
+        }
+        ["validate", code] => {
+            // Escaneo en la entrada: el boleto solo se acepta una vez
+            match write_seats(seating_structure).check_in(code, orders::now()) {
+                Ok(order) => format!(
+                    "VALID {} {} {} seat {}",
+                    order.ticket_id, order.typ, order.zone, order.number
+                ),
+                Err(e) => e.reply(),
+            }
+        }
+        ["attendance"] => stats::format_attendance(&read_seats(seating_structure)),
+        ["release", typ, zone, number] => {
+            // Con un solo candado: libera el asiento, anula la compra si la había y lo ofrece a la lista de espera
+            let number = match parse_number(number) {
+                Ok(number) => number,
+                Err(e) => return e.reply(),
+            };
+            match write_seats(seating_structure).operator_release(typ, zone, number) {
+                Ok(Some((order_id, amount))) => {
+                    format!("OK {} {} {} status=Free refunded order {} amount {}", typ, zone, number, order_id, amount)
+                }
+                Ok(None) => format!("OK {} {} {} status=Free", typ, zone, number),
+                Err(e) => e.reply(),
+            }
+        }
+        ["block", typ, zone, number] => update_seat(seating_structure, typ, zone, number, |seat| {
+            // Un asiento vendido se libera primero, así su orden queda reembolsada
+            if seat.status == SeatStatus::Purchased {
+                return Err(TicketingError::SeatNotFree(SeatRef::new(typ, zone, seat.number)));
+            }
+            seat.status = SeatStatus::Blocked;
+            seat.holder = None;
+            Ok(())
+        }),
+        ["vision", typ, zone, number, percentage] => {
+            let percentage = match percentage.parse::<u8>() {
+                Ok(percentage) if percentage <= 100 => percentage,
+                _ => return TicketingError::InvalidArgument(format!("Invalid vision percentage: {}", percentage)).reply(),
+            };
+            update_seat(seating_structure, typ, zone, number, |seat| {
+                seat.vision_percentage = percentage;
+                Ok(())
+            })
+        }
+        ["price", typ, zone, number, price] => {
+            let price = match price.parse::<u32>() {
+                Ok(price) => price,
+                Err(_) => return TicketingError::InvalidArgument(format!("Invalid price: {}", price)).reply(),
+            };
+            update_seat(seating_structure, typ, zone, number, |seat| {
+                seat.price = price;
+                Ok(())
+            })
+        }
+        ["open", typ] | ["close", typ] => {
+            if *typ != "VIP" && *typ != "General" {
+                return TicketingError::InvalidCategory(typ.to_string()).reply();
+            }
+            let mut seating_structure = write_seats(seating_structure);
+            seating_structure.closed_sales.retain(|closed| closed != typ);
+            if parts[0] == "close" {
+                seating_structure.closed_sales.push(typ.to_string());
+                format!("Sales closed for {}.", typ)
+            } else {
+                seating_structure.offer_freed_seats();
+                format!("Sales opened for {}.", typ)
+            }
+        }
+        ["reload"] => {
+            let path = match &config.venue_file {
+                Some(path) => path,
+                None => return TicketingError::NoVenueFile.reply(),
+            };
+            match storage::load_state(path) {
+                Ok(venue) => {
+                    write_seats(seating_structure).reload_venue(venue);
+                    format!("Venue reloaded from {}.", path)
+                }
+                Err(e) => TicketingError::VenueReloadFailed(e.to_string()).reply(),
+            }
+        }
+        ["shutdown"] => {
+            shutdown.store(true, Ordering::SeqCst);
+            "Server shutting down.".to_string()
+        }
+        _ => format!("{}\n{}", TicketingError::InvalidCommand(command.to_string()).reply(), HELP),
+    }
+}
+
+fn update_seat<F>(
+    seating_structure: &RwLock<SeatingStructure>,
+    typ: &str,
+    zone: &str,
+    number: &str,
+    update: F,
+) -> String
+where
+    F: FnOnce(&mut Seat) -> Result<(), TicketingError>,
+{
+    // Busca el asiento y le aplica el cambio con el candado de escritura tomado
+    let number = match parse_number(number) {
+        Ok(number) => number,
+        Err(e) => return e.reply(),
+    };
+    let mut seating_structure = write_seats(seating_structure);
+    let seat = match seating_structure.seat_mut(typ, zone, number) {
+        Some(seat) => seat,
+        None => return TicketingError::SeatNotFound(SeatRef::new(typ, zone, number)).reply(),
+    };
+    let response = match update(seat) {
+        Ok(()) => format!(
+            "OK {} {} {} status={:?} vision={} price={}",
+            typ, zone, number, seat.status, seat.vision_percentage, seat.price
+        ),
+        Err(e) => return e.reply(),
+    };
+    // Un asiento bloqueado deja sin valor la oferta de lista de espera que lo incluía
+    if seating_structure.withdraw_stale_offers() > 0 {
+        seating_structure.offer_freed_seats();
+    }
+    response
+}
+
+fn parse_number(number: &str) -> Result<u32, TicketingError> {
+    number.parse().map_err(|_| TicketingError::InvalidArgument(format!("Invalid seat number: {}", number)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(command: &str, seating_structure: &RwLock<SeatingStructure>) -> String {
+        execute(command, &ServerConfig::new(), seating_structure, &AtomicBool::new(false))
+    }
+
+    #[test]
+    fn token_matches_only_the_configured_token() {
+        assert!(token_matches("s3cret", "s3cret"));
+        assert!(!token_matches("s3cret", "s3cre"));
+        assert!(!token_matches("s3cret", "s3cret "));
+        assert!(!token_matches("s3cret", ""));
+    }
+
+    #[test]
+    fn close_and_open_toggle_sales() {
+        let seating_structure = RwLock::new(SeatingStructure::new());
+        assert_eq!(run("close VIP", &seating_structure), "Sales closed for VIP.");
+        assert!(!read_seats(&seating_structure).sales_open("VIP"));
+        assert_eq!(run("open VIP", &seating_structure), "Sales opened for VIP.");
+        assert!(read_seats(&seating_structure).sales_open("VIP"));
+    }
+
+    #[test]
+    fn unknown_commands_reply_with_the_help() {
+        let seating_structure = RwLock::new(SeatingStructure::new());
+        let response = run("fly", &seating_structure);
+        assert!(response.starts_with("ERR INVALID_COMMAND"));
+        assert!(response.contains("map [vision] [plain] [features]"));
+    }
+
+    #[test]
+    fn block_withdraws_the_waitlist_offer_of_the_seat() {
+        let seating_structure = RwLock::new(SeatingStructure::new());
+        // Con asientos libres la oferta llega al entrar a la fila
+        write_seats(&seating_structure).join_waitlist(1, "VIP", 1).unwrap();
+        let (zone, number) = read_seats(&seating_structure).waitlist.offer(1).unwrap().seats[0].clone();
+        assert!(run(&format!("block VIP {} {}", zone, number), &seating_structure).starts_with("OK "));
+        // La oferta se retira y el cliente recibe otro asiento libre
+        let seats = read_seats(&seating_structure);
+        let offer = seats.waitlist.offer(1).unwrap();
+        assert_ne!(offer.seats[0], (zone, number));
+    }
+}
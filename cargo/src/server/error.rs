@@ -0,0 +1,270 @@
+use super::{SeatFailure, MAX_SEATS_PER_REQUEST};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeatRef {
+    /**
+    Asiento al que se refiere un error, se muestra como `VIP ZonaA 2`.
+    */
+    pub typ: String,
+    pub zone: String,
+    pub number: u32,
+}
+
+impl SeatRef {
+    pub fn new(typ: &str, zone: &str, number: u32) -> Self {
+        SeatRef { typ: typ.to_string(), zone: zone.to_string(), number }
+    }
+}
+
+impl fmt::Display for SeatRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.typ, self.zone, self.number)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TicketingError {
+    /**
+    Fallas que el servidor le responde a los clientes (TCP, HTTP y WebSocket)
+    y a los operadores del canal de administración.
+    Cada una tiene un código estable que no cambia aunque cambie el mensaje,
+    ver `code`.
+    */
+    InvalidCommand(String),
+    InvalidArgument(String),
+    InvalidCategory(String),
+    InvalidSeatCount,
+    SalesClosed(String),
+    SeatNotFound(SeatRef),
+    SeatNotFree(SeatRef),
+    SeatNotHeld(SeatRef),
+    CompanionRequired(SeatRef),
+    SeatsUnavailable(Vec<SeatFailure>),
+    NoSeatsFound,
+    OrderNotFound(u64),
+    OrderNotOwned(u64),
+    NothingToRefund(SeatRef),
+    RefundsDisabled,
+    RefundDeadlinePassed,
+    AlreadyRefunded(u64),
+    ForgedTicket(String),
+    WrongEvent(String),
+    UnknownTicket(String),
+    TicketRefunded(String),
+    TicketVoid(String),
+    AlreadyCheckedIn(String, u64),
+    AlreadyOnWaitlist,
+    NotOnWaitlist,
+    NoOffer,
+    NotSubscribed,
+    MalformedRequest(String),
+    SessionRequired,
+    UnknownSession,
+    NotFound(String),
+    MethodNotAllowed(String),
+    ServerBusy,
+    ShuttingDown,
+    AuthenticationFailed,
+    NoVenueFile,
+    VenueReloadFailed(String),
+    StorageUnavailable(String),
+}
+
+impl TicketingError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TicketingError::InvalidCommand(_) => "INVALID_COMMAND",
+            TicketingError::InvalidArgument(_) => "INVALID_ARGUMENT",
+            TicketingError::InvalidCategory(_) => "INVALID_CATEGORY",
+            TicketingError::InvalidSeatCount => "INVALID_SEAT_COUNT",
+            TicketingError::SalesClosed(_) => "SALES_CLOSED",
+            TicketingError::SeatNotFound(_) => "SEAT_NOT_FOUND",
+            TicketingError::SeatNotFree(_) => "SEAT_NOT_FREE",
+            TicketingError::SeatNotHeld(_) => "SEAT_NOT_HELD",
+            TicketingError::CompanionRequired(_) => "COMPANION_REQUIRED",
+            TicketingError::SeatsUnavailable(_) => "SEATS_UNAVAILABLE",
+            TicketingError::NoSeatsFound => "NO_SEATS_FOUND",
+            TicketingError::OrderNotFound(_) => "ORDER_NOT_FOUND",
+            TicketingError::OrderNotOwned(_) => "ORDER_NOT_OWNED",
+            TicketingError::NothingToRefund(_) => "NOTHING_TO_REFUND",
+            TicketingError::RefundsDisabled => "REFUNDS_DISABLED",
+            TicketingError::RefundDeadlinePassed => "REFUND_DEADLINE_PASSED",
+            TicketingError::AlreadyRefunded(_) => "ALREADY_REFUNDED",
+            TicketingError::ForgedTicket(_) => "FORGED_TICKET",
+            TicketingError::WrongEvent(_) => "WRONG_EVENT",
+            TicketingError::UnknownTicket(_) => "UNKNOWN_TICKET",
+            TicketingError::TicketRefunded(_) => "TICKET_REFUNDED",
+            TicketingError::TicketVoid(_) => "TICKET_VOID",
+            TicketingError::AlreadyCheckedIn(..) => "ALREADY_CHECKED_IN",
+            TicketingError::AlreadyOnWaitlist => "ALREADY_ON_WAITLIST",
+            TicketingError::NotOnWaitlist => "NOT_ON_WAITLIST",
+            TicketingError::NoOffer => "NO_OFFER",
+            TicketingError::NotSubscribed => "NOT_SUBSCRIBED",
+            TicketingError::MalformedRequest(_) => "MALFORMED_REQUEST",
+            TicketingError::SessionRequired => "SESSION_REQUIRED",
+            TicketingError::UnknownSession => "UNKNOWN_SESSION",
+            TicketingError::NotFound(_) => "NOT_FOUND",
+            TicketingError::MethodNotAllowed(_) => "METHOD_NOT_ALLOWED",
+            TicketingError::ServerBusy => "SERVER_BUSY",
+            TicketingError::ShuttingDown => "SHUTTING_DOWN",
+            TicketingError::AuthenticationFailed => "AUTHENTICATION_FAILED",
+            TicketingError::NoVenueFile => "NO_VENUE_FILE",
+            TicketingError::VenueReloadFailed(_) => "VENUE_RELOAD_FAILED",
+            TicketingError::StorageUnavailable(_) => "STORAGE_UNAVAILABLE",
+        }
+    }
+
+    pub fn reply(&self) -> String {
+        // Respuesta del protocolo TCP: `ERR <CODIGO> <mensaje>`
+        format!("ERR {} {}", self.code(), self)
+    }
+}
+
+impl fmt::Display for TicketingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TicketingError::InvalidCommand(command) => write!(f, "Invalid command: {}", command),
+            TicketingError::InvalidArgument(message) => write!(f, "{}", message),
+            TicketingError::InvalidCategory(typ) => write!(f, "Invalid category: {}", typ),
+            TicketingError::InvalidSeatCount => {
+                write!(f, "Seat count must be between 1 and {}.", MAX_SEATS_PER_REQUEST)
+            }
+            TicketingError::SalesClosed(typ) => write!(f, "Sales are closed for {}.", typ),
+            TicketingError::SeatNotFound(seat) => write!(f, "Seat not found: {}", seat),
+            TicketingError::SeatNotFree(seat) => write!(f, "Seat {} is not free.", seat),
+            TicketingError::SeatNotHeld(seat) => write!(f, "Seat {} is not reserved by you.", seat),
+            TicketingError::CompanionRequired(seat) => write!(
+                f,
+                "Seat {} is a wheelchair space, book an adjacent companion seat first or pick both together.",
+                seat
+            ),
+            TicketingError::SeatsUnavailable(failures) => {
+                let failures = failures
+                    .iter()
+                    .map(|failure| format!("({},{}) {}", failure.zone, failure.number, failure.reason))
+                    .collect::<Vec<String>>()
+                    .join("; ");
+                write!(f, "Some seats are not available, nothing was held: {}", failures)
+            }
+            TicketingError::NoSeatsFound => write!(f, "No suitable seats found."),
+            TicketingError::OrderNotFound(order_id) => write!(f, "Order not found: {}", order_id),
+            TicketingError::OrderNotOwned(order_id) => write!(f, "Order {} does not belong to you.", order_id),
+            TicketingError::NothingToRefund(seat) => write!(f, "Seat {} has no purchase to refund.", seat),
+            TicketingError::RefundsDisabled => write!(f, "Refunds are not allowed for this event."),
+            TicketingError::RefundDeadlinePassed => write!(f, "Refund deadline has passed."),
+            TicketingError::AlreadyRefunded(order_id) => write!(f, "Order {} was already refunded.", order_id),
+            TicketingError::ForgedTicket(reason) => write!(f, "Forged ticket: {}", reason),
+            TicketingError::WrongEvent(event) => write!(f, "Ticket is for another event: {}", event),
+            TicketingError::UnknownTicket(ticket_id) => write!(f, "Unknown ticket: {}", ticket_id),
+            TicketingError::TicketRefunded(ticket_id) => write!(f, "Ticket {} was refunded", ticket_id),
+            TicketingError::TicketVoid(ticket_id) => {
+                write!(f, "Ticket {} no longer holds its seat, it was released or sold again", ticket_id)
+            }
+            TicketingError::AlreadyCheckedIn(ticket_id, at) => write!(f, "Ticket {} was already scanned at {}", ticket_id, at),
+            TicketingError::AlreadyOnWaitlist => write!(f, "You are already on the waitlist."),
+            TicketingError::NotOnWaitlist => write!(f, "You are not on the waitlist."),
+            TicketingError::NoOffer => write!(f, "You have no waitlist offer to claim."),
+            TicketingError::NotSubscribed => write!(f, "You are not subscribed."),
+            TicketingError::MalformedRequest(message) => write!(f, "{}", message),
+            TicketingError::SessionRequired => write!(f, "Missing X-Session header."),
+            TicketingError::UnknownSession => write!(f, "Unknown or expired session."),
+            TicketingError::NotFound(path) => write!(f, "Not found: {}", path),
+            TicketingError::MethodNotAllowed(method) => write!(f, "Method not allowed: {}", method),
+            TicketingError::ServerBusy => write!(f, "Server busy. Try again later."),
+            TicketingError::ShuttingDown => write!(f, "Server shutting down."),
+            TicketingError::AuthenticationFailed => write!(f, "Authentication failed."),
+            TicketingError::NoVenueFile => write!(f, "No venue file configured."),
+            TicketingError::VenueReloadFailed(error) => write!(f, "Failed to reload venue: {}", error),
+            TicketingError::StorageUnavailable(path) => {
+                write!(f, "Sales are paused, the saved state {} could not be loaded.", path)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply_starts_with_the_stable_code() {
+        let seat = SeatRef::new("VIP", "ZonaA", 2);
+        assert_eq!(TicketingError::SeatNotFree(seat.clone()).reply(), "ERR SEAT_NOT_FREE Seat VIP ZonaA 2 is not free.");
+        assert_eq!(TicketingError::NoSeatsFound.reply(), "ERR NO_SEATS_FOUND No suitable seats found.");
+        assert_eq!(
+            TicketingError::InvalidSeatCount.reply(),
+            format!("ERR INVALID_SEAT_COUNT Seat count must be between 1 and {}.", MAX_SEATS_PER_REQUEST)
+        );
+        // Los mensajes libres se muestran tal cual después del código
+        assert_eq!(
+            TicketingError::InvalidArgument("Missing seat list".to_string()).reply(),
+            "ERR INVALID_ARGUMENT Missing seat list"
+        );
+    }
+
+    #[test]
+    fn seats_unavailable_lists_every_failed_seat() {
+        let failures = vec![
+            SeatFailure { zone: "ZonaA".to_string(), number: 1, reason: "purchased".to_string() },
+            SeatFailure { zone: "ZonaD".to_string(), number: 9, reason: "not found".to_string() },
+        ];
+        assert_eq!(
+            TicketingError::SeatsUnavailable(failures).reply(),
+            "ERR SEATS_UNAVAILABLE Some seats are not available, nothing was held: (ZonaA,1) purchased; (ZonaD,9) not found"
+        );
+    }
+
+    #[test]
+    fn codes_are_distinct_and_do_not_depend_on_the_message() {
+        let seat = SeatRef::new("General", "ZonaB", 1);
+        let errors = [
+            TicketingError::InvalidCommand("x".to_string()),
+            TicketingError::InvalidArgument("x".to_string()),
+            TicketingError::InvalidCategory("x".to_string()),
+            TicketingError::InvalidSeatCount,
+            TicketingError::SalesClosed("x".to_string()),
+            TicketingError::SeatNotFound(seat.clone()),
+            TicketingError::SeatNotFree(seat.clone()),
+            TicketingError::SeatNotHeld(seat.clone()),
+            TicketingError::CompanionRequired(seat.clone()),
+            TicketingError::SeatsUnavailable(vec![]),
+            TicketingError::NoSeatsFound,
+            TicketingError::OrderNotFound(1),
+            TicketingError::OrderNotOwned(1),
+            TicketingError::NothingToRefund(seat),
+            TicketingError::RefundsDisabled,
+            TicketingError::RefundDeadlinePassed,
+            TicketingError::AlreadyRefunded(1),
+            TicketingError::ForgedTicket("x".to_string()),
+            TicketingError::WrongEvent("x".to_string()),
+            TicketingError::UnknownTicket("x".to_string()),
+            TicketingError::TicketRefunded("x".to_string()),
+            TicketingError::TicketVoid("x".to_string()),
+            TicketingError::AlreadyCheckedIn("x".to_string(), 1),
+            TicketingError::AlreadyOnWaitlist,
+            TicketingError::NotOnWaitlist,
+            TicketingError::NoOffer,
+            TicketingError::NotSubscribed,
+            TicketingError::MalformedRequest("x".to_string()),
+            TicketingError::SessionRequired,
+            TicketingError::UnknownSession,
+            TicketingError::NotFound("x".to_string()),
+            TicketingError::MethodNotAllowed("x".to_string()),
+            TicketingError::ServerBusy,
+            TicketingError::ShuttingDown,
+            TicketingError::AuthenticationFailed,
+            TicketingError::NoVenueFile,
+            TicketingError::VenueReloadFailed("x".to_string()),
+            TicketingError::StorageUnavailable("x".to_string()),
+        ];
+        let mut codes: Vec<&str> = errors.iter().map(|error| error.code()).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(
+            TicketingError::InvalidCategory("Palco".to_string()).code(),
+            TicketingError::InvalidCategory("Campo".to_string()).code()
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use super::storage::StorageStatus;
+use super::{SeatingStructure, END_MARKER};
+use std::time::Duration;
+
+// Versión del servidor, la misma del paquete
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Clone, Debug)]
+pub struct Health {
+    /**
+    Estado del servidor para el balanceador y el orquestador:
+    - `version`: Versión del servidor.
+    - `uptime`: Tiempo en marcha.
+    - `event`: Nombre del evento; el servidor atiende uno solo, así que `events` siempre es 1.
+    - `storage`: Resultado de cargar el estado guardado.
+    - `sales_open`: Si la venta está abierta, por tipo de asiento.
+    */
+    pub version: &'static str,
+    pub uptime: Duration,
+    pub event: String,
+    pub events: usize,
+    pub storage: StorageStatus,
+    pub sales_open: Vec<(&'static str, bool)>,
+}
+
+impl Health {
+    pub fn ready(&self) -> bool {
+        // Si el estado guardado no se pudo leer, el servidor corre con la distribución inicial
+        // y no debe recibir tráfico hasta que un operador lo revise
+        !matches!(self.storage, StorageStatus::Failed { .. })
+    }
+
+    pub fn status(&self) -> &'static str {
+        if self.ready() {
+            "ready"
+        } else {
+            "not-ready"
+        }
+    }
+
+    pub fn format(&self) -> String {
+        /**
+        Respuesta del comando `health`, una línea por dato y `END_MARKER` al final:
+        `status ready`, `version 0.1.0`, `uptime 42`, `events 1 concierto`,
+        `storage loaded estado.txt` y `sales VIP=open General=closed`.
+        */
+        let storage = match &self.storage {
+            StorageStatus::Defaults => "defaults".to_string(),
+            StorageStatus::Loaded(path) => format!("loaded {}", path),
+            StorageStatus::Failed { path, error } => format!("failed {} ({})", path, error),
+        };
+        let sales: Vec<String> = self
+            .sales_open
+            .iter()
+            .map(|(typ, open)| format!("{}={}", typ, if *open { "open" } else { "closed" }))
+            .collect();
+        format!(
+            "status {}\nversion {}\nuptime {}\nevents {} {}\nstorage {}\nsales {}\n{}\n",
+            self.status(),
+            self.version,
+            self.uptime.as_secs(),
+            self.events,
+            self.event,
+            storage,
+            sales.join(" "),
+            END_MARKER
+        )
+    }
+}
+
+pub fn check(seating_structure: &SeatingStructure) -> Health {
+    Health {
+        version: VERSION,
+        uptime: seating_structure.started_at.elapsed(),
+        event: seating_structure.orders.event.clone(),
+        events: 1,
+        storage: seating_structure.storage.clone(),
+        sales_open: ["VIP", "General"]
+            .into_iter()
+            .map(|typ| (typ, seating_structure.sales_open(typ)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed() -> StorageStatus {
+        StorageStatus::Failed { path: "estado.txt".to_string(), error: "line 3: bad seat".to_string() }
+    }
+
+    #[test]
+    fn ready_unless_the_saved_state_failed_to_load() {
+        let mut seating_structure = SeatingStructure::new();
+        assert!(check(&seating_structure).ready());
+        seating_structure.storage = StorageStatus::Loaded("estado.txt".to_string());
+        assert!(check(&seating_structure).ready());
+        seating_structure.storage = failed();
+        let health = check(&seating_structure);
+        assert!(!health.ready());
+        assert_eq!(health.status(), "not-ready");
+    }
+
+    #[test]
+    fn format_lists_every_field_and_ends_with_the_marker() {
+        let mut seating_structure = SeatingStructure::new();
+        seating_structure.orders.event = "concierto".to_string();
+        seating_structure.closed_sales.push("General".to_string());
+        seating_structure.storage = failed();
+        let health = Health { uptime: Duration::from_secs(42), ..check(&seating_structure) };
+        assert_eq!(
+            health.format(),
+            format!(
+                "status not-ready\nversion {}\nuptime 42\nevents 1 concierto\nstorage failed estado.txt (line 3: bad seat)\nsales VIP=open General=closed\n{}\n",
+                VERSION, END_MARKER
+            )
+        );
+    }
+}
@@ -0,0 +1,785 @@
+use super::error::{SeatRef, TicketingError};
+use super::health;
+use super::logging;
+use super::metrics;
+use super::pool::ConnectionSlot;
+use super::search::FindConstraints;
+use super::storage::StorageStatus;
+use super::{read_seats, stats, write_seats, Seat, SeatingStructure, SHUTDOWN_POLL_INTERVAL};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Tiempo máximo para recibir una petición completa
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Tamaño máximo del cuerpo de una petición
+const MAX_BODY: usize = 4096;
+
+// Largo máximo de la línea de la petición o de un encabezado, y de todos juntos
+const MAX_HEADER_LINE: usize = 8192;
+const MAX_HEADERS: usize = 32 * 1024;
+
+// Conexiones HTTP atendidas a la vez, cada una ocupa un hilo
+const MAX_CONNECTIONS: usize = 64;
+
+// Recursos de la API, los que atiende `Gateway::route`
+const RESOURCES: [&str; 7] = ["health", "events", "availability", "find", "holds", "purchases", "orders"];
+
+struct Request {
+    /**
+    Petición HTTP ya leída:
+    - `method`, `path`: Línea de la petición, sin la consulta.
+    - `query`: Parámetros después de `?`.
+    - `session`: Valor del encabezado `X-Session`.
+    - `body`: Cuerpo de la petición.
+    */
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    session: Option<String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    body: Value,
+    session: Option<String>,
+}
+
+impl Response {
+    fn new(status: u16, body: Value) -> Self {
+        Response { status, body, session: None }
+    }
+
+    fn error(error: TicketingError) -> Self {
+        /**
+        Respuesta de error con el mensaje y el código estable de `TicketingError`.
+        Una selección que falló trae además el motivo de cada asiento.
+        */
+        let mut body = json!({ "error": error.to_string(), "code": error.code() });
+        if let TicketingError::SeatsUnavailable(failures) = &error {
+            body["failures"] = failures
+                .iter()
+                .map(|failure| json!({ "zone": failure.zone, "number": failure.number, "reason": failure.reason }))
+                .collect();
+        }
+        Response::new(status(&error), body)
+    }
+}
+
+impl From<TicketingError> for Response {
+    fn from(error: TicketingError) -> Self {
+        Response::error(error)
+    }
+}
+
+fn status(error: &TicketingError) -> u16 {
+    match error {
+        TicketingError::InvalidArgument(_)
+        | TicketingError::InvalidCategory(_)
+        | TicketingError::InvalidSeatCount
+        | TicketingError::MalformedRequest(_) => 400,
+        TicketingError::SessionRequired | TicketingError::UnknownSession => 401,
+        TicketingError::OrderNotOwned(_) => 403,
+        TicketingError::InvalidCommand(_)
+        | TicketingError::NotFound(_)
+        | TicketingError::SeatNotFound(_)
+        | TicketingError::OrderNotFound(_)
+        | TicketingError::NoSeatsFound => 404,
+        TicketingError::MethodNotAllowed(_) => 405,
+        TicketingError::ServerBusy | TicketingError::StorageUnavailable(_) => 503,
+        _ => 409,
+    }
+}
+
+struct Session {
+    /**
+    Cliente HTTP identificado por su clave `X-Session`:
+    - `holder`: Dueño de sus reservas, igual que el id de una conexión TCP.
+    - `last_seen`: Última petición, para liberar las reservas de sesiones abandonadas.
+    */
+    holder: u64,
+    last_seen: Instant,
+}
+
+struct Gateway {
+    seating_structure: Arc<RwLock<SeatingStructure>>,
+    sessions: Mutex<HashMap<String, Session>>,
+    next_holder: Arc<AtomicU64>,
+    hold_timeout: Duration,
+}
+
+pub fn start_http(
+    address: String,
+    seating_structure: Arc<RwLock<SeatingStructure>>,
+    next_holder: Arc<AtomicU64>,
+    hold_timeout: Duration,
+    shutdown: Arc<AtomicBool>,
+) -> io::Result<()> {
+    /**
+    Inicia la API HTTP/JSON en su propio hilo, cada petición se atiende en un hilo aparte.
+
+    Rutas:
+    - `GET /events`
+    - `GET /availability?type=VIP&zone=ZonaA`
+    - `GET /find?type=VIP&count=2`, con las restricciones de `find`: `same_zone=true&min_vision=90`
+    - `POST /holds` con `{"type": "VIP", "zone": "ZonaA", "number": 2}`
+      o varios asientos a la vez con `{"type": "VIP", "seats": [{"zone": "ZonaA", "number": 2}, ...]}`
+    - `DELETE /holds/<tipo>/<zona>/<numero>`
+    - `POST /purchases` con el mismo cuerpo que `/holds`
+    - `GET /orders/<id>`
+
+    Las reservas y compras pertenecen a una sesión: la primera respuesta trae el
+    encabezado `X-Session` y el cliente lo envía en las siguientes peticiones.
+    Las reservas de una sesión sin peticiones por `hold_timeout` se liberan.
+
+    Se atienden a lo más `MAX_CONNECTIONS` peticiones a la vez, las demás reciben 503.
+    */
+    let listener = TcpListener::bind(&address)?;
+    listener.set_nonblocking(true)?;
+    logging::info("HTTP API is listening", json!({ "address": address }));
+
+    let gateway = Arc::new(Gateway { seating_structure, sessions: Mutex::new(HashMap::new()), next_holder, hold_timeout });
+    // Las sesiones vencen con su propio reloj, aunque el listener nunca esté ocioso
+    let expiry_gateway = Arc::clone(&gateway);
+    let expiry_shutdown = Arc::clone(&shutdown);
+    thread::spawn(move || {
+        while !expiry_shutdown.load(Ordering::SeqCst) {
+            expiry_gateway.expire_sessions();
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+    });
+
+    let active_connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        while !shutdown.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((mut stream, _)) => {
+                    let slot = match ConnectionSlot::acquire(&active_connections, MAX_CONNECTIONS) {
+                        Some(slot) => slot,
+                        None => {
+                            logging::warn("HTTP API busy, connection rejected", json!({ "max_connections": MAX_CONNECTIONS }));
+                            let busy = stream
+                                .set_nonblocking(false)
+                                .and_then(|()| write_response(&mut stream, &TicketingError::ServerBusy.into()));
+                            if let Err(e) = busy {
+                                logging::warn("Failed to reject HTTP connection", json!({ "error": e.to_string() }));
+                            }
+                            continue;
+                        }
+                    };
+                    let gateway = Arc::clone(&gateway);
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        if let Err(e) = handle_http(stream, &gateway) {
+                            logging::warn("HTTP connection error", json!({ "error": e.to_string() }));
+                        }
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(SHUTDOWN_POLL_INTERVAL),
+                Err(e) => logging::warn("Failed to accept HTTP connection", json!({ "error": e.to_string() })),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle_http(stream: TcpStream, gateway: &Gateway) -> io::Result<()> {
+    // Una petición por conexión, la respuesta cierra la conexión
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    metrics::connection_opened("http");
+    let request_id = logging::next_request_id();
+    let started = Instant::now();
+    let (method, path, response) = match read_request(&mut BufReader::new(stream)) {
+        Ok(request) => (request.method.clone(), request.path.clone(), gateway.route(&request)),
+        Err(e) => (String::new(), String::new(), Response::error(TicketingError::MalformedRequest(e))),
+    };
+    logging::info(
+        "HTTP request",
+        json!({
+            "req": request_id,
+            "method": method,
+            "path": path,
+            "status": response.status,
+            "outcome": response.body["code"].as_str().unwrap_or("ok"),
+            "duration_us": started.elapsed().as_micros() as u64,
+        }),
+    );
+    // En las métricas cada recurso cuenta por su primer segmento, sin ids
+    let resource = path.trim_matches('/').split('/').next().unwrap_or("");
+    let resource = RESOURCES.iter().find(|known| **known == resource).copied();
+    metrics::command("http", resource.unwrap_or("unknown"), response.body["code"].as_str().unwrap_or("ok"));
+    let written = write_response(&mut writer, &response);
+    metrics::connection_closed("http");
+    written
+}
+
+fn read_line(reader: &mut impl BufRead, read: &mut usize) -> Result<String, String> {
+    /**
+    Lee una línea de la cabecera sin pasar de `MAX_HEADER_LINE` ni del total `MAX_HEADERS`,
+    así un cliente no puede llenar la memoria con una línea sin fin.
+    `read` acumula lo leído en la petición.
+    */
+    let limit = MAX_HEADER_LINE.min(MAX_HEADERS.saturating_sub(*read)) + 1;
+    let mut line = String::new();
+    reader.by_ref().take(limit as u64).read_line(&mut line).map_err(|e| e.to_string())?;
+    *read += line.len();
+    if line.len() >= limit && !line.ends_with('\n') {
+        return Err("Request headers are too large.".to_string());
+    }
+    Ok(line)
+}
+
+fn percent_decode(value: &str) -> Result<String, String> {
+    // Valor de la consulta: `%XX` es un byte en hexadecimal y `+` un espacio
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut chars = value.bytes();
+    while let Some(byte) = chars.next() {
+        match byte {
+            b'%' => {
+                let hex = [chars.next(), chars.next()];
+                let decoded = match hex {
+                    [Some(high), Some(low)] => std::str::from_utf8(&[high, low]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                    _ => None,
+                };
+                bytes.push(decoded.ok_or("Malformed query string.")?);
+            }
+            b'+' => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| "Malformed query string.".to_string())
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, String> {
+    let mut read = 0;
+    let line = read_line(reader, &mut read)?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err("Malformed request line.".to_string()),
+    };
+
+    let mut content_length = 0;
+    let mut session = None;
+    loop {
+        let header = read_line(reader, &mut read)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').ok_or("Malformed header.")?;
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.trim().parse().map_err(|_| "Invalid Content-Length.")?,
+            "x-session" => session = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err("Request body is too large.".to_string());
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let query = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| Ok((percent_decode(key)?, percent_decode(value)?)))
+        .collect::<Result<HashMap<String, String>, String>>()?;
+    Ok(Request { method, path: path.to_string(), query, session, body })
+}
+
+fn write_response(writer: &mut impl Write, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        body.len()
+    );
+    if let Some(session) = &response.session {
+        head.push_str(&format!("X-Session: {}\r\n", session));
+    }
+    head.push_str("\r\n");
+    writer.write_all(head.as_bytes())?;
+    writer.write_all(body.as_bytes())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+pub fn seat_json(typ: &str, zone: &str, seat: &Seat) -> Value {
+    // Un asiento en JSON, también lo usa el WebSocket
+    json!({
+        "type": typ,
+        "zone": zone,
+        "number": seat.number,
+        "price": seat.price,
+        "vision": seat.vision_percentage,
+        "status": format!("{:?}", seat.status),
+        "features": seat.features.names(),
+    })
+}
+
+fn seats_json(seats: &[(String, u32)]) -> Vec<Value> {
+    seats.iter().map(|(zone, number)| json!({ "zone": zone, "number": number })).collect()
+}
+
+fn missing_field(name: &str) -> TicketingError {
+    TicketingError::InvalidArgument(format!("Missing field: {}", name))
+}
+
+fn invalid_field(name: &str, expected: &str) -> TicketingError {
+    TicketingError::InvalidArgument(format!("Invalid field: {} must be {}", name, expected))
+}
+
+fn string_field<'a>(body: &'a Value, name: &str) -> Result<&'a str, TicketingError> {
+    // Campo de texto del cuerpo: si falta o si tiene otro tipo son errores distintos
+    match &body[name] {
+        Value::Null => Err(missing_field(name)),
+        value => value.as_str().ok_or_else(|| invalid_field(name, "a string")),
+    }
+}
+
+pub fn seat_number(value: &Value) -> Result<u32, TicketingError> {
+    // Número de asiento del cuerpo, uno que no cabe en `u32` se rechaza en lugar de truncarse
+    let number = match value {
+        Value::Null => return Err(missing_field("number")),
+        value => value.as_u64().ok_or_else(|| invalid_field("number", "a seat number"))?,
+    };
+    u32::try_from(number).map_err(|_| TicketingError::InvalidArgument(format!("Invalid seat number: {}", number)))
+}
+
+fn seat_request(body: &[u8]) -> Result<(String, String, u32), Response> {
+    // Cuerpo de `/holds` y `/purchases`: {"type": "VIP", "zone": "ZonaA", "number": 2}
+    let body: Value =
+        serde_json::from_slice(body).map_err(|e| TicketingError::MalformedRequest(format!("Invalid JSON: {}", e)))?;
+    let typ = string_field(&body, "type")?;
+    let zone = string_field(&body, "zone")?;
+    let number = seat_number(&body["number"])?;
+    if typ != "VIP" && typ != "General" {
+        return Err(TicketingError::InvalidCategory(typ.to_string()).into());
+    }
+    Ok((typ.to_string(), zone.to_string(), number))
+}
+
+impl Gateway {
+    fn route(&self, request: &Request) -> Response {
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        let result = match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["health"]) => Ok(self.health()),
+            ("GET", ["events"]) => Ok(self.events()),
+            ("GET", ["availability"]) => self.availability(request),
+            ("GET", ["find"]) => self.find(request),
+            ("POST", ["holds"]) => self.hold(request),
+            ("DELETE", ["holds", typ, zone, number]) => self.release(request, typ, zone, number),
+            ("POST", ["purchases"]) => self.purchase(request),
+            ("GET", ["orders", id]) => self.order(request, id),
+            (_, ["health"] | ["events"] | ["availability"] | ["find"] | ["holds", ..] | ["purchases"] | ["orders", _]) => {
+                Err(TicketingError::MethodNotAllowed(request.method.clone()).into())
+            }
+            _ => Err(TicketingError::NotFound(request.path.clone()).into()),
+        };
+        result.unwrap_or_else(|response| response)
+    }
+
+    fn session(&self, request: &Request, create: bool) -> Result<(String, u64), Response> {
+        /**
+        Busca la sesión de la petición. Si no trae una y `create` es verdadero,
+        crea una nueva que se devuelve en el encabezado `X-Session`.
+        */
+        let mut sessions = self.sessions.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(token) = &request.session {
+            return match sessions.get_mut(token) {
+                Some(session) => {
+                    session.last_seen = Instant::now();
+                    Ok((token.clone(), session.holder))
+                }
+                None => Err(TicketingError::UnknownSession.into()),
+            };
+        }
+        if !create {
+            return Err(TicketingError::SessionRequired.into());
+        }
+        let token: String = (0..16).map(|_| format!("{:02x}", rand::thread_rng().gen::<u8>())).collect();
+        let holder = self.next_holder.fetch_add(1, Ordering::SeqCst);
+        sessions.insert(token.clone(), Session { holder, last_seen: Instant::now() });
+        Ok((token, holder))
+    }
+
+    fn expire_sessions(&self) {
+        // Libera las reservas de las sesiones abandonadas, como al desconectarse por TCP
+        let expired: Vec<u64> = {
+            let mut sessions = self.sessions.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let expired = sessions
+                .values()
+                .filter(|session| session.last_seen.elapsed() > self.hold_timeout)
+                .map(|session| session.holder)
+                .collect();
+            sessions.retain(|_, session| session.last_seen.elapsed() <= self.hold_timeout);
+            expired
+        };
+        for holder in expired {
+            let released = write_seats(&self.seating_structure).release_holds(holder);
+            metrics::holds_expired(released);
+            logging::info("HTTP session expired", json!({ "conn": holder, "released": released }));
+        }
+    }
+
+    fn health(&self) -> Response {
+        // 503 mientras el servidor no esté listo, así el balanceador no le manda tráfico
+        let health = health::check(&read_seats(&self.seating_structure));
+        let storage = match &health.storage {
+            StorageStatus::Defaults => json!({ "status": health.storage.name() }),
+            StorageStatus::Loaded(path) => json!({ "status": health.storage.name(), "path": path }),
+            StorageStatus::Failed { path, error } => json!({ "status": health.storage.name(), "path": path, "error": error }),
+        };
+        let sales_open: serde_json::Map<String, Value> =
+            health.sales_open.iter().map(|(typ, open)| (typ.to_string(), Value::from(*open))).collect();
+        Response::new(
+            if health.ready() { 200 } else { 503 },
+            json!({
+                "status": health.status(),
+                "version": health.version,
+                "uptime_seconds": health.uptime.as_secs(),
+                "event": health.event,
+                "events": health.events,
+                "storage": storage,
+                "sales_open": sales_open,
+            }),
+        )
+    }
+
+    fn events(&self) -> Response {
+        let seating_structure = read_seats(&self.seating_structure);
+        let availability = stats::availability(&seating_structure, None, None);
+        Response::new(
+            200,
+            json!({
+                "events": [{
+                    "name": seating_structure.orders.event,
+                    "sales_open": {
+                        "VIP": seating_structure.sales_open("VIP"),
+                        "General": seating_structure.sales_open("General"),
+                    },
+                    "seats": availability.total,
+                    "free": availability.free,
+                }]
+            }),
+        )
+    }
+
+    fn availability(&self, request: &Request) -> Result<Response, Response> {
+        let typ = request.query.get("type").map(String::as_str);
+        let zone = request.query.get("zone").map(String::as_str);
+        if typ.is_some_and(|typ| typ != "VIP" && typ != "General") {
+            return Err(TicketingError::InvalidCategory(typ.unwrap_or("").to_string()).into());
+        }
+        let seating_structure = read_seats(&self.seating_structure);
+        let zone_exists = |name: &str| {
+            seating_structure.categories.iter().any(|category| category.zones.iter().any(|zone| zone.name == name))
+        };
+        if zone.is_some_and(|zone| !zone_exists(zone)) {
+            return Err(TicketingError::NotFound(format!("zone {}", zone.unwrap_or(""))).into());
+        }
+        let availability = stats::availability(&seating_structure, typ, zone);
+        Ok(Response::new(
+            200,
+            json!({
+                "type": typ,
+                "zone": zone,
+                "total": availability.total,
+                "free": availability.free,
+                "reserved": availability.reserved,
+                "purchased": availability.purchased,
+                "blocked": availability.blocked,
+                "sold_percentage": availability.sell_through(),
+                "average_free_vision": availability.average_free_vision(),
+            }),
+        ))
+    }
+
+    fn find(&self, request: &Request) -> Result<Response, Response> {
+        let typ = request.query.get("type").map(String::as_str).unwrap_or("");
+        if typ != "VIP" && typ != "General" {
+            return Err(TicketingError::InvalidCategory(typ.to_string()).into());
+        }
+        let count = match request.query.get("count").and_then(|count| count.parse::<u32>().ok()) {
+            Some(count) if (1..=5).contains(&count) => count,
+            _ => return Err(TicketingError::InvalidSeatCount.into()),
+        };
+        // Las restricciones son las mismas opciones de `find`: same_zone=true, min_vision=90, exclude=ZonaC
+        let options: Vec<String> = request
+            .query
+            .iter()
+            .filter(|(key, _)| *key != "type" && *key != "count")
+            .map(|(key, value)| match value.as_str() {
+                "true" => key.replace('_', "-"),
+                _ => format!("{}={}", key.replace('_', "-"), value),
+            })
+            .collect();
+        let constraints = FindConstraints::from_args(options.iter().map(String::as_str))?;
+        let seating_structure = read_seats(&self.seating_structure);
+        if !seating_structure.sales_open(typ) {
+            return Err(TicketingError::SalesClosed(typ.to_string()).into());
+        }
+        let searching = Instant::now();
+        let combination = seating_structure.find_seats(typ, count, &constraints)?;
+        if combination.is_empty() {
+            let alternatives: Vec<Value> = seating_structure
+                .suggest_alternatives(typ, count, &constraints)
+                .iter()
+                .enumerate()
+                .map(|(rank, alternative)| {
+                    json!({
+                        "rank": rank + 1,
+                        "kind": alternative.kind.name(),
+                        "type": alternative.typ,
+                        "seats": seats_json(&alternative.seats),
+                    })
+                })
+                .collect();
+            let mut response = Response::error(TicketingError::NoSeatsFound);
+            response.body["alternatives"] = alternatives.into();
+            metrics::find_duration(searching.elapsed());
+            return Err(response);
+        }
+        metrics::find_duration(searching.elapsed());
+        Ok(Response::new(200, json!({ "type": typ, "seats": seats_json(&combination) })))
+    }
+
+    fn hold(&self, request: &Request) -> Result<Response, Response> {
+        let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+        if body["seats"].is_array() {
+            return self.hold_selection(request, &body);
+        }
+        let (typ, zone, number) = seat_request(&request.body)?;
+        let (token, holder) = self.session(request, true)?;
+        let mut seating_structure = write_seats(&self.seating_structure);
+        seating_structure.hold_seat(&typ, &zone, number, holder)?;
+        let seat = seating_structure
+            .seat_mut(&typ, &zone, number)
+            .ok_or_else(|| TicketingError::SeatNotFound(SeatRef::new(&typ, &zone, number)))?;
+        let body = json!({ "session": token, "seat": seat_json(&typ, &zone, seat) });
+        Ok(Response { status: 201, body, session: Some(token) })
+    }
+
+    fn hold_selection(&self, request: &Request, body: &Value) -> Result<Response, Response> {
+        /**
+        Reserva una selección de asientos, todos o ninguno:
+        `{"type": "VIP", "seats": [{"zone": "ZonaB", "number": 2}, {"zone": "ZonaB", "number": 3}]}`
+
+        Si algún asiento no está disponible responde 409 con el motivo de cada uno.
+        */
+        let typ = string_field(body, "type")?;
+        let seats = body["seats"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|seat| match (seat["zone"].as_str(), seat["number"].as_u64()) {
+                (Some(zone), Some(_)) => Ok((zone.to_string(), seat_number(&seat["number"])?)),
+                _ => Err(Response::error(TicketingError::InvalidArgument("Each seat needs zone and number.".to_string()))),
+            })
+            .collect::<Result<Vec<(String, u32)>, Response>>()?;
+        let (token, holder) = self.session(request, true)?;
+        let mut seating_structure = write_seats(&self.seating_structure);
+        seating_structure.hold_seats(typ, &seats, holder)?;
+        let held: Vec<Value> = seats
+            .iter()
+            .filter_map(|(zone, number)| seating_structure.seat_mut(typ, zone, *number).map(|seat| seat_json(typ, zone, seat)))
+            .collect();
+        Ok(Response { status: 201, body: json!({ "session": token, "seats": held }), session: Some(token) })
+    }
+
+    fn release(&self, request: &Request, typ: &str, zone: &str, number: &str) -> Result<Response, Response> {
+        let (_, holder) = self.session(request, false)?;
+        let number = number
+            .parse::<u32>()
+            .map_err(|_| TicketingError::InvalidArgument(format!("Invalid seat number: {}", number)))?;
+        write_seats(&self.seating_structure).release_seat(typ, zone, number, holder)?;
+        Ok(Response::new(200, json!({ "released": { "type": typ, "zone": zone, "number": number } })))
+    }
+
+    fn purchase(&self, request: &Request) -> Result<Response, Response> {
+        let (typ, zone, number) = seat_request(&request.body)?;
+        let (token, holder) = self.session(request, true)?;
+        let mut seating_structure = write_seats(&self.seating_structure);
+        if !seating_structure.sales_open(&typ) {
+            return Err(TicketingError::SalesClosed(typ).into());
+        }
+        // `purchase_seat` solo acepta un asiento libre o reservado por la misma sesión
+        let order_id = seating_structure.purchase_seat(&typ, &zone, number, holder)?;
+        let orders = &seating_structure.orders;
+        let body = match orders.order(order_id) {
+            Some(order) => json!({
+                "session": token,
+                "order": order_id,
+                "ticket_id": order.ticket_id,
+                "price": order.price,
+                "code": orders.ticket_code(order),
+            }),
+            None => json!({ "session": token, "order": order_id }),
+        };
+        Ok(Response { status: 201, body, session: Some(token) })
+    }
+
+    fn order(&self, request: &Request, id: &str) -> Result<Response, Response> {
+        let (_, holder) = self.session(request, false)?;
+        let id = id
+            .parse::<u64>()
+            .map_err(|_| TicketingError::InvalidArgument(format!("Invalid order number: {}", id)))?;
+        let seating_structure = read_seats(&self.seating_structure);
+        let orders = &seating_structure.orders;
+        let order = orders.order(id).ok_or(TicketingError::OrderNotFound(id))?;
+        if order.holder != Some(holder) {
+            return Err(TicketingError::OrderNotOwned(id).into());
+        }
+        Ok(Response::new(
+            200,
+            json!({
+                "order": order.id,
+                "ticket_id": order.ticket_id,
+                "event": orders.event,
+                "type": order.typ,
+                "zone": order.zone,
+                "number": order.number,
+                "price": order.price,
+                "purchased_at": order.purchased_at,
+                "refund": order.refund.as_ref().map(|refund| json!({ "amount": refund.amount, "refunded_at": refund.refunded_at })),
+                "checked_in_at": order.checked_in_at,
+                "code": orders.ticket_code(order),
+            }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn gateway() -> Gateway {
+        Gateway {
+            seating_structure: Arc::new(RwLock::new(SeatingStructure::new())),
+            sessions: Mutex::new(HashMap::new()),
+            next_holder: Arc::new(AtomicU64::new(1)),
+            hold_timeout: Duration::from_secs(60),
+        }
+    }
+
+    fn request(method: &str, target: &str, session: Option<&str>, body: &str) -> Request {
+        // Petición armada como llegaría por el socket
+        let session = session.map_or(String::new(), |session| format!("X-Session: {}\r\n", session));
+        let raw = format!("{} {} HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}", method, target, session, body.len(), body);
+        read_request(&mut Cursor::new(raw)).unwrap()
+    }
+
+    #[test]
+    fn read_request_decodes_the_query_and_reads_the_body() {
+        let request = request("GET", "/find?type=VIP&count=2&zones=Zona%41%2CZonaB&x=a+b", Some("s1"), "{}");
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("GET", "/find"));
+        assert_eq!(request.query.get("zones").map(String::as_str), Some("ZonaA,ZonaB"));
+        assert_eq!(request.query.get("x").map(String::as_str), Some("a b"));
+        assert_eq!(request.session.as_deref(), Some("s1"));
+        assert_eq!(request.body, b"{}");
+
+        let malformed = read_request(&mut Cursor::new("GET /find?type=%G1 HTTP/1.1\r\n\r\n"));
+        assert_eq!(malformed.err(), Some("Malformed query string.".to_string()));
+    }
+
+    #[test]
+    fn read_request_limits_the_header_size() {
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEADER_LINE));
+        assert_eq!(read_request(&mut Cursor::new(long_line)).err(), Some("Request headers are too large.".to_string()));
+
+        let header = format!("X-Filler: {}\r\n", "a".repeat(1000));
+        let many_headers = format!("GET /events HTTP/1.1\r\n{}\r\n", header.repeat(MAX_HEADERS / header.len() + 1));
+        assert_eq!(read_request(&mut Cursor::new(many_headers)).err(), Some("Request headers are too large.".to_string()));
+    }
+
+    #[test]
+    fn route_answers_unknown_paths_and_methods() {
+        let gateway = gateway();
+        assert_eq!(gateway.route(&request("GET", "/events", None, "")).status, 200);
+        assert_eq!(gateway.route(&request("GET", "/nowhere", None, "")).status, 404);
+        let response = gateway.route(&request("DELETE", "/events", None, ""));
+        assert_eq!((response.status, response.body["code"].as_str()), (405, Some("METHOD_NOT_ALLOWED")));
+        assert_eq!(gateway.route(&request("GET", "/availability?type=Palco", None, "")).status, 400);
+        assert_eq!(gateway.route(&request("GET", "/orders/1", None, "")).status, 401);
+    }
+
+    #[test]
+    fn hold_and_purchase_belong_to_a_session() {
+        let gateway = gateway();
+        let seat = r#"{"type": "VIP", "zone": "ZonaB", "number": 2}"#;
+        let held = gateway.route(&request("POST", "/holds", None, seat));
+        assert_eq!(held.status, 201);
+        let session = held.session.unwrap();
+
+        // Otra sesión no puede comprar el asiento reservado
+        let other = gateway.route(&request("POST", "/purchases", None, seat));
+        assert_eq!((other.status, other.body["code"].as_str()), (409, Some("SEAT_NOT_FREE")));
+
+        let purchased = gateway.route(&request("POST", "/purchases", Some(&session), seat));
+        assert_eq!(purchased.status, 201);
+        let order = purchased.body["order"].as_u64().unwrap();
+        let path = format!("/orders/{}", order);
+        assert_eq!(gateway.route(&request("GET", &path, Some(&session), "")).status, 200);
+        let other_seat = r#"{"type": "VIP", "zone": "ZonaB", "number": 3}"#;
+        let other_session = gateway.route(&request("POST", "/holds", None, other_seat)).session.unwrap();
+        assert_eq!(gateway.route(&request("GET", &path, Some(&other_session), "")).status, 403);
+        assert_eq!(gateway.route(&request("GET", &path, Some("unknown"), "")).status, 401);
+    }
+
+    #[test]
+    fn seat_request_tells_missing_and_invalid_fields_apart() {
+        let missing = seat_request(br#"{"type": "VIP", "zone": "ZonaB"}"#).err().unwrap();
+        assert_eq!((missing.status, missing.body["error"].as_str()), (400, Some("Missing field: number")));
+        let invalid = seat_request(br#"{"type": "VIP", "zone": "ZonaB", "number": "2"}"#).err().unwrap();
+        assert_eq!(invalid.body["error"].as_str(), Some("Invalid field: number must be a seat number"));
+        let invalid = seat_request(br#"{"type": 1, "zone": "ZonaB", "number": 2}"#).err().unwrap();
+        assert_eq!(invalid.body["error"].as_str(), Some("Invalid field: type must be a string"));
+        let too_big = seat_request(br#"{"type": "VIP", "zone": "ZonaB", "number": 4294967296}"#).err().unwrap();
+        assert_eq!(too_big.body["error"].as_str(), Some("Invalid seat number: 4294967296"));
+    }
+
+    #[test]
+    fn health_is_unavailable_while_the_saved_state_failed_to_load() {
+        let gateway = gateway();
+        let response = gateway.route(&request("GET", "/health", None, ""));
+        assert_eq!((response.status, response.body["status"].as_str()), (200, Some("ready")));
+        assert_eq!(response.body["storage"], json!({ "status": "defaults" }));
+        assert_eq!(response.body["sales_open"], json!({ "VIP": true, "General": true }));
+
+        gateway.seating_structure.write().unwrap().storage =
+            StorageStatus::Failed { path: "estado.txt".to_string(), error: "line 3: bad seat".to_string() };
+        let response = gateway.route(&request("GET", "/health", None, ""));
+        assert_eq!((response.status, response.body["status"].as_str()), (503, Some("not-ready")));
+        assert_eq!(
+            response.body["storage"],
+            json!({ "status": "failed", "path": "estado.txt", "error": "line 3: bad seat" })
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    /**
+    Nivel de un mensaje del registro, de menor a mayor gravedad:
+    - Debug: Detalle para depurar, por ejemplo el texto de cada petición.
+    - Info: Funcionamiento normal, una línea por petición atendida.
+    - Warn: Algo falló pero el servidor sigue (una conexión, un archivo).
+    - Error: El servidor no puede hacer lo que se le pidió.
+    */
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogConfig {
+    /**
+    Configuración del registro:
+    - `level`: Nivel mínimo que se escribe.
+    - `file`: Archivo del registro, `None` para escribir en stderr.
+    - `max_size`: Tamaño en bytes a partir del cual se rota el archivo.
+    - `keep`: Cantidad de archivos rotados que se conservan (`archivo.1` es el más reciente).
+    */
+    pub level: Level,
+    pub file: Option<String>,
+    pub max_size: u64,
+    pub keep: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig { level: Level::Info, file: None, max_size: 10 * 1024 * 1024, keep: 5 }
+    }
+}
+
+struct RotatingFile {
+    path: String,
+    file: File,
+    size: u64,
+    max_size: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    fn open(path: &str, max_size: u64, keep: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { path: path.to_string(), file, size, max_size, keep })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        // archivo.N se descarta, archivo.1 pasa a archivo.2 y el actual pasa a archivo.1
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.keep).rev() {
+                let from = format!("{}.{}", self.path, index);
+                if fs::metadata(&from).is_ok() {
+                    fs::rename(&from, format!("{}.{}", self.path, index + 1))?;
+                }
+            }
+            fs::rename(&self.path, format!("{}.1", self.path))?;
+        }
+        *self = RotatingFile::open(&self.path, self.max_size, self.keep)?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+enum Output {
+    Stderr,
+    File(RotatingFile),
+}
+
+struct Logger {
+    level: Level,
+    output: Mutex<Output>,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+// Ids de petición únicos en todo el servidor (TCP, HTTP, WebSocket y administración)
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+pub fn init(config: &LogConfig) -> io::Result<()> {
+    /**
+    Configura el registro del servidor. Solo la primera llamada tiene efecto;
+    si nunca se llama, se escribe en stderr desde el nivel `Info`.
+    */
+    let output = match &config.file {
+        Some(path) => Output::File(RotatingFile::open(path, config.max_size, config.keep)?),
+        None => Output::Stderr,
+    };
+    let _ = LOGGER.set(Logger { level: config.level, output: Mutex::new(output) });
+    Ok(())
+}
+
+fn logger() -> &'static Logger {
+    LOGGER.get_or_init(|| Logger { level: Level::Info, output: Mutex::new(Output::Stderr) })
+}
+
+pub fn next_request_id() -> u64 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub fn enabled(level: Level) -> bool {
+    level >= logger().level
+}
+
+pub fn log(level: Level, message: &str, fields: Value) {
+    /**
+    Escribe una línea JSON con la hora, el nivel, el mensaje y los campos extra,
+    por ejemplo `{"ts":"2024-05-01T20:15:03.120Z","level":"info","msg":"request","conn":4,...}`.
+    */
+    if !enabled(level) {
+        return;
+    }
+    let mut entry = json!({ "ts": timestamp(), "level": level.name(), "msg": message });
+    if let (Some(entry), Value::Object(fields)) = (entry.as_object_mut(), fields) {
+        entry.extend(fields);
+    }
+    let line = format!("{}\n", entry);
+
+    let mut output = logger().output.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let written = match &mut *output {
+        Output::Stderr => io::stderr().write_all(line.as_bytes()),
+        Output::File(file) => file.write_line(&line),
+    };
+    if let Err(e) = written {
+        // Si el archivo falla, el mensaje no se pierde
+        eprint!("Failed to write log ({}): {}", e, line);
+    }
+}
+
+pub fn debug(message: &str, fields: Value) {
+    log(Level::Debug, message, fields);
+}
+
+pub fn info(message: &str, fields: Value) {
+    log(Level::Info, message, fields);
+}
+
+pub fn warn(message: &str, fields: Value) {
+    log(Level::Warn, message, fields);
+}
+
+pub fn error(message: &str, fields: Value) {
+    log(Level::Error, message, fields);
+}
+
+fn timestamp() -> String {
+    // Hora UTC en formato RFC 3339 con milisegundos, sin depender de una biblioteca de fechas
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = elapsed.as_secs();
+    let (days, time) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // Días desde 1970-01-01 a fecha civil (algoritmo de Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn temp_path(name: &str) -> String {
+        // Archivo propio de cada prueba, las pruebas corren en paralelo
+        env::temp_dir()
+            .join(format!("event_ticketing_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn remove_logs(path: &str, keep: usize) {
+        let _ = fs::remove_file(path);
+        for index in 1..=keep + 1 {
+            let _ = fs::remove_file(format!("{}.{}", path, index));
+        }
+    }
+
+    #[test]
+    fn rotates_before_exceeding_max_size_and_keeps_the_newest_files() {
+        let path = temp_path("rotate.log");
+        remove_logs(&path, 2);
+        // Cada línea ocupa 10 bytes: entran dos por archivo
+        let mut file = RotatingFile::open(&path, 25, 2).unwrap();
+        for line in ["line-001\n", "line-002\n", "line-003\n", "line-004\n", "line-005\n", "line-006\n", "line-007\n"]
+        {
+            file.write_line(line).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line-007\n");
+        assert_eq!(fs::read_to_string(format!("{}.1", path)).unwrap(), "line-005\nline-006\n");
+        assert_eq!(fs::read_to_string(format!("{}.2", path)).unwrap(), "line-003\nline-004\n");
+        // Las primeras líneas quedaron en el archivo que se descartó
+        assert!(fs::metadata(format!("{}.3", path)).is_err());
+        remove_logs(&path, 2);
+    }
+
+    #[test]
+    fn keeps_counting_an_existing_file_and_rotates_without_backups() {
+        let path = temp_path("rotate_keep0.log");
+        remove_logs(&path, 0);
+        fs::write(&path, "old-line-01\n").unwrap();
+        let mut file = RotatingFile::open(&path, 16, 0).unwrap();
+        file.write_line("new-line\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new-line\n");
+        assert!(fs::metadata(format!("{}.1", path)).is_err());
+        remove_logs(&path, 0);
+    }
+
+    #[test]
+    fn a_line_larger_than_max_size_still_goes_to_an_empty_file() {
+        let path = temp_path("rotate_large.log");
+        remove_logs(&path, 1);
+        let mut file = RotatingFile::open(&path, 4, 1).unwrap();
+        file.write_line("larger-than-max\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "larger-than-max\n");
+        assert!(fs::metadata(format!("{}.1", path)).is_err());
+        remove_logs(&path, 1);
+    }
+}
@@ -0,0 +1,308 @@
+use super::pool::ConnectionSlot;
+use super::{logging, SHUTDOWN_POLL_INTERVAL};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+// Conexiones de métricas atendidas a la vez, Prometheus abre una por lectura
+const MAX_CONNECTIONS: usize = 8;
+
+// Límites de los histogramas de tiempo, en segundos
+const LATENCY_BUCKETS: [f64; 12] = [0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25];
+
+// Familias que se exportan, en el orden en que aparecen: nombre, tipo y descripción
+const FAMILIES: [(&str, &str, &str); 9] = [
+    ("ticketing_connections_total", "counter", "Connections accepted, by protocol."),
+    ("ticketing_connections_active", "gauge", "Connections currently open, by protocol."),
+    ("ticketing_commands_total", "counter", "Commands handled, by protocol, command and outcome."),
+    ("ticketing_find_duration_seconds", "histogram", "Time spent searching for seats."),
+    ("ticketing_lock_wait_seconds", "histogram", "Time spent waiting for the seating lock, by mode."),
+    ("ticketing_holds_created_total", "counter", "Seats held for a client, including waitlist offers."),
+    ("ticketing_holds_expired_total", "counter", "Held seats released without a purchase by a disconnect, session timeout or unclaimed offer."),
+    ("ticketing_seats_sold_total", "counter", "Seats sold, by category."),
+    ("ticketing_revenue_total", "counter", "Revenue from sold seats, by category."),
+];
+
+#[derive(Clone, Default)]
+struct Histogram {
+    /**
+    Histograma acumulado: `counts[i]` cuenta las observaciones que no superan
+    `LATENCY_BUCKETS[i]`, igual que el `le` de Prometheus.
+    */
+    counts: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    /**
+    Valores de las métricas, indexados por nombre de la familia y etiquetas ya
+    formateadas (`protocol="tcp",command="find"`). Los contadores y medidores
+    comparten el mapa; solo los medidores bajan.
+    */
+    values: BTreeMap<(&'static str, String), f64>,
+    histograms: BTreeMap<(&'static str, String), Histogram>,
+}
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY
+        .get_or_init(|| Mutex::new(Registry::default()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    // Los valores se escapan como pide el formato de texto de Prometheus
+    pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn add(name: &'static str, pairs: &[(&str, &str)], delta: f64) {
+    *registry().values.entry((name, labels(pairs))).or_insert(0.0) += delta;
+}
+
+fn observe(name: &'static str, pairs: &[(&str, &str)], elapsed: Duration) {
+    registry()
+        .histograms
+        .entry((name, labels(pairs)))
+        .or_default()
+        .observe(elapsed.as_secs_f64());
+}
+
+pub fn connection_opened(protocol: &str) {
+    add("ticketing_connections_total", &[("protocol", protocol)], 1.0);
+    add("ticketing_connections_active", &[("protocol", protocol)], 1.0);
+}
+
+pub fn connection_closed(protocol: &str) {
+    add("ticketing_connections_active", &[("protocol", protocol)], -1.0);
+}
+
+pub fn command(protocol: &str, command: &str, outcome: &str) {
+    /**
+    Cuenta un comando atendido. `outcome` es `ok` o el código de `TicketingError`;
+    quien llama debe pasar un nombre de comando conocido o `unknown`,
+    así un cliente no puede crear series nuevas a voluntad.
+    */
+    add("ticketing_commands_total", &[("protocol", protocol), ("command", command), ("outcome", outcome)], 1.0);
+}
+
+pub fn find_duration(elapsed: Duration) {
+    observe("ticketing_find_duration_seconds", &[], elapsed);
+}
+
+pub fn lock_wait(mode: &str, elapsed: Duration) {
+    observe("ticketing_lock_wait_seconds", &[("mode", mode)], elapsed);
+}
+
+pub fn holds_created(count: usize) {
+    add("ticketing_holds_created_total", &[], count as f64);
+}
+
+pub fn holds_expired(count: usize) {
+    add("ticketing_holds_expired_total", &[], count as f64);
+}
+
+pub fn seat_sold(typ: &str, price: u32) {
+    add("ticketing_seats_sold_total", &[("category", typ)], 1.0);
+    add("ticketing_revenue_total", &[("category", typ)], price as f64);
+}
+
+pub fn render() -> String {
+    /**
+    Exporta todas las métricas en el formato de texto de Prometheus (versión 0.0.4).
+    */
+    let registry = registry();
+    let mut output = String::new();
+    for (family, kind, help) in FAMILIES {
+        output.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", family, help, family, kind));
+        for ((_, labels), value) in registry.values.iter().filter(|((name, _), _)| *name == family) {
+            output.push_str(&sample(family, labels, *value));
+        }
+        for ((_, labels), histogram) in registry.histograms.iter().filter(|((name, _), _)| *name == family) {
+            let bucket_name = format!("{}_bucket", family);
+            let separator = if labels.is_empty() { "" } else { "," };
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.counts) {
+                let labels = format!("{}{}le=\"{}\"", labels, separator, bound);
+                output.push_str(&sample(&bucket_name, &labels, count as f64));
+            }
+            let labels_inf = format!("{}{}le=\"+Inf\"", labels, separator);
+            output.push_str(&sample(&bucket_name, &labels_inf, histogram.count as f64));
+            output.push_str(&sample(&format!("{}_sum", family), labels, histogram.sum));
+            output.push_str(&sample(&format!("{}_count", family), labels, histogram.count as f64));
+        }
+    }
+    output
+}
+
+fn sample(name: &str, labels: &str, value: f64) -> String {
+    if labels.is_empty() {
+        format!("{} {}\n", name, value)
+    } else {
+        format!("{}{{{}}} {}\n", name, labels, value)
+    }
+}
+
+pub fn start_metrics(address: String, shutdown: Arc<AtomicBool>) -> io::Result<()> {
+    /**
+    Inicia el puerto de métricas en su propio hilo. Solo responde `GET /metrics`,
+    pensado para que lo lea Prometheus desde la misma máquina o red interna.
+    Con `MAX_CONNECTIONS` lecturas en curso, las siguientes se cierran sin respuesta.
+    */
+    let listener = TcpListener::bind(&address)?;
+    listener.set_nonblocking(true)?;
+    logging::info("Metrics are listening", json!({ "address": address }));
+
+    let active_connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        while !shutdown.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let slot = match ConnectionSlot::acquire(&active_connections, MAX_CONNECTIONS) {
+                        Some(slot) => slot,
+                        None => {
+                            logging::warn("Metrics busy, connection rejected", json!({ "max_connections": MAX_CONNECTIONS }));
+                            continue;
+                        }
+                    };
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        if let Err(e) = handle_metrics(stream) {
+                            logging::warn("Metrics connection error", json!({ "error": e.to_string() }));
+                        }
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(SHUTDOWN_POLL_INTERVAL),
+                Err(e) => logging::warn("Failed to accept metrics connection", json!({ "error": e.to_string() })),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle_metrics(stream: TcpStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Se descartan los encabezados, no hacen falta para responder
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && header.trim() != "" {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render()),
+        (Some(_), Some("/metrics")) => ("405 Method Not Allowed", "Method not allowed.\n".to_string()),
+        _ => ("404 Not Found", "Not found.\n".to_string()),
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    // El registro es global y otras pruebas también lo usan: cada prueba usa etiquetas propias
+
+    #[test]
+    fn render_lists_every_family_in_order() {
+        let output = render();
+        let mut position = 0;
+        for (family, kind, help) in FAMILIES {
+            let header = format!("# HELP {} {}\n# TYPE {} {}\n", family, help, family, kind);
+            let found = output[position..].find(&header).unwrap_or_else(|| panic!("{} missing or out of order", family));
+            position += found + header.len();
+        }
+    }
+
+    #[test]
+    fn counters_and_gauges_are_rendered_with_their_labels() {
+        connection_opened("metrics_test");
+        connection_opened("metrics_test");
+        connection_closed("metrics_test");
+        command("metrics_test", "find", "NO_SEATS_FOUND");
+        let output = render();
+        assert!(output.contains("ticketing_connections_total{protocol=\"metrics_test\"} 2\n"));
+        assert!(output.contains("ticketing_connections_active{protocol=\"metrics_test\"} 1\n"));
+        assert!(output
+            .contains("ticketing_commands_total{protocol=\"metrics_test\",command=\"find\",outcome=\"NO_SEATS_FOUND\"} 1\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(labels(&[("mode", "a\"b\\c\nd"), ("protocol", "tcp")]), "mode=\"a\\\"b\\\\c\\nd\",protocol=\"tcp\"");
+        assert_eq!(labels(&[]), "");
+    }
+
+    #[test]
+    fn histograms_render_cumulative_buckets_sum_and_count() {
+        lock_wait("metrics_test", Duration::from_micros(300));
+        lock_wait("metrics_test", Duration::from_secs(1));
+        let output = render();
+        let name = "ticketing_lock_wait_seconds";
+        assert!(output.contains(&format!("{}_bucket{{mode=\"metrics_test\",le=\"0.00025\"}} 0\n", name)));
+        assert!(output.contains(&format!("{}_bucket{{mode=\"metrics_test\",le=\"0.0005\"}} 1\n", name)));
+        assert!(output.contains(&format!("{}_bucket{{mode=\"metrics_test\",le=\"0.25\"}} 1\n", name)));
+        assert!(output.contains(&format!("{}_bucket{{mode=\"metrics_test\",le=\"+Inf\"}} 2\n", name)));
+        assert!(output.contains(&format!("{}_sum{{mode=\"metrics_test\"}} 1.0003\n", name)));
+        assert!(output.contains(&format!("{}_count{{mode=\"metrics_test\"}} 2\n", name)));
+    }
+
+    fn get(request: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        handle_metrics(stream).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn only_get_metrics_is_served() {
+        let response = get("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n"));
+        assert!(response.contains("# TYPE ticketing_seats_sold_total counter\n"));
+        assert!(get("POST /metrics HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(get("GET /health HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
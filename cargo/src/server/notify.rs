@@ -0,0 +1,241 @@
+use super::waitlist::Offer;
+use super::{SeatStatus, SeatingStructure};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /**
+    Qué cambios recibe un suscriptor:
+    - `types`: Tipos de asiento ("VIP" o "General"), vacío para todos.
+    - `zones`: Nombres de zona, vacío para todas.
+    */
+    pub types: Vec<String>,
+    pub zones: Vec<String>,
+}
+
+impl Filter {
+    pub fn from_args<'a>(args: impl Iterator<Item = &'a str>, seating_structure: &SeatingStructure) -> Result<Self, String> {
+        // Argumentos del comando `subscribe`: cualquier mezcla de tipos y zonas
+        let mut filter = Filter::default();
+        for arg in args {
+            if arg == "VIP" || arg == "General" {
+                filter.types.push(arg.to_string());
+            } else if seating_structure
+                .categories
+                .iter()
+                .any(|category| category.zones.iter().any(|zone| zone.name == arg))
+            {
+                filter.zones.push(arg.to_string());
+            } else {
+                return Err(format!("Invalid category or zone: {}", arg));
+            }
+        }
+        Ok(filter)
+    }
+
+    fn matches(&self, typ: &str, zone: &str) -> bool {
+        (self.types.is_empty() || self.types.iter().any(|t| t == typ))
+            && (self.zones.is_empty() || self.zones.iter().any(|z| z == zone))
+    }
+
+    pub fn describe(&self) -> String {
+        let scope: Vec<&str> = self.types.iter().chain(self.zones.iter()).map(String::as_str).collect();
+        if scope.is_empty() {
+            "all seats".to_string()
+        } else {
+            scope.join(" ")
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    /**
+    Cambio que se envía a los suscriptores:
+    - Seat: Un asiento cambió de estado.
+    - HoldExpired: Venció la oferta de la lista de espera que apartaba el asiento.
+    - Offer: Asientos ofrecidos al suscriptor desde la lista de espera.
+    */
+    Seat { typ: String, zone: String, number: u32, status: SeatStatus },
+    HoldExpired { typ: String, zone: String, number: u32 },
+    Offer { typ: String, seats: Vec<(String, u32)>, claim_window: u64 },
+}
+
+impl Event {
+    pub fn line(&self) -> String {
+        // Formato del protocolo TCP: una línea `EVENT ...` por evento
+        match self {
+            Event::Seat { typ, zone, number, status } => format!("EVENT seat {} {} {} {:?}\n", typ, zone, number, status),
+            Event::HoldExpired { typ, zone, number } => format!("EVENT hold_expired {} {} {}\n", typ, zone, number),
+            Event::Offer { typ, seats, claim_window } => {
+                let seats = seats
+                    .iter()
+                    .map(|(zone, number)| format!("({},{})", zone, number))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                format!("EVENT offer {} {} claim within {}s\n", typ, seats, claim_window)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    holder: u64,
+    filter: Filter,
+    sender: Sender<Event>,
+}
+
+#[derive(Debug, Default)]
+pub struct Notifier {
+    /**
+    Conexiones suscritas a los cambios de asientos.
+    Cada conexión recibe sus eventos por un canal y los escribe en su propio hilo,
+    así publicar nunca bloquea a quien tiene el candado de escritura.
+    */
+    subscribers: Vec<Subscriber>,
+}
+
+impl Notifier {
+    pub fn subscribe(&mut self, holder: u64, filter: Filter) -> Receiver<Event> {
+        // Una suscripción nueva reemplaza la anterior de la misma conexión
+        self.unsubscribe(holder);
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(Subscriber { holder, filter, sender });
+        receiver
+    }
+
+    pub fn unsubscribe(&mut self, holder: u64) -> bool {
+        let subscribed = self.subscribers.len();
+        self.subscribers.retain(|subscriber| subscriber.holder != holder);
+        subscribed != self.subscribers.len()
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    fn publish(&mut self, typ: &str, zone: &str, event: Event) {
+        // Los suscriptores cuya conexión ya terminó se descartan
+        self.subscribers.retain(|subscriber| {
+            !subscriber.filter.matches(typ, zone) || subscriber.sender.send(event.clone()).is_ok()
+        });
+    }
+
+    pub fn seat_changed(&mut self, typ: &str, zone: &str, number: u32, status: &SeatStatus) {
+        let event = Event::Seat { typ: typ.to_string(), zone: zone.to_string(), number, status: status.clone() };
+        self.publish(typ, zone, event);
+    }
+
+    pub fn hold_expired(&mut self, typ: &str, zone: &str, number: u32) {
+        let event = Event::HoldExpired { typ: typ.to_string(), zone: zone.to_string(), number };
+        self.publish(typ, zone, event);
+    }
+
+    pub fn offer(&mut self, offer: &Offer, claim_window: u64) {
+        // La oferta solo le llega a su dueño, sin importar el filtro
+        let event = Event::Offer { typ: offer.typ.clone(), seats: offer.seats.clone(), claim_window };
+        if let Some(subscriber) = self.subscribers.iter().find(|subscriber| subscriber.holder == offer.holder) {
+            let _ = subscriber.sender.send(event);
+        }
+    }
+}
+
+pub type SeatSnapshot = Vec<(&'static str, String, u32, SeatStatus)>;
+
+pub fn snapshot(seating_structure: &SeatingStructure) -> SeatSnapshot {
+    // Estado de cada asiento, para comparar antes y después de un cambio
+    let mut seats = Vec::new();
+    for category in &seating_structure.categories {
+        for zone in &category.zones {
+            for (typ, zone_seats) in [("VIP", &zone.Vip.seats), ("General", &zone.General.seats)] {
+                for seat in zone_seats {
+                    seats.push((typ, zone.name.clone(), seat.number, seat.status.clone()));
+                }
+            }
+        }
+    }
+    seats
+}
+
+pub fn publish_changes(seating_structure: &mut SeatingStructure, before: &SeatSnapshot) {
+    /**
+    Publica un evento por cada asiento que cambió de estado desde `before`.
+    Los asientos nuevos o eliminados al recargar el lugar no se publican.
+    */
+    for (typ, zone, number, status) in snapshot(seating_structure) {
+        let changed = before
+            .iter()
+            .find(|(t, z, n, _)| *t == typ && *z == zone && *n == number)
+            .is_some_and(|(_, _, _, previous)| *previous != status);
+        if changed {
+            seating_structure.notifier.seat_changed(typ, &zone, number, &status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn filter(args: &str) -> Filter {
+        Filter::from_args(args.split_whitespace(), &SeatingStructure::new()).unwrap()
+    }
+
+    #[test]
+    fn filter_reads_types_and_zones() {
+        let vip_zone_b = filter("VIP ZonaB");
+        assert!(vip_zone_b.matches("VIP", "ZonaB"));
+        assert!(!vip_zone_b.matches("General", "ZonaB"));
+        assert!(!vip_zone_b.matches("VIP", "ZonaA"));
+        assert_eq!(vip_zone_b.describe(), "VIP ZonaB");
+        assert_eq!(filter("").describe(), "all seats");
+        assert!(Filter::from_args(["Palco"].into_iter(), &SeatingStructure::new()).is_err());
+    }
+
+    #[test]
+    fn subscribers_receive_only_matching_seat_changes() {
+        let mut seating_structure = SeatingStructure::new();
+        let vip = seating_structure.notifier.subscribe(1, filter("VIP"));
+        let zone_c = seating_structure.notifier.subscribe(2, filter("ZonaC"));
+
+        let before = snapshot(&seating_structure);
+        seating_structure.hold_seat("VIP", "ZonaB", 2, 3).unwrap();
+        seating_structure.hold_seat("General", "ZonaC", 1, 3).unwrap();
+        publish_changes(&mut seating_structure, &before);
+
+        let lines: Vec<String> = vip.try_iter().map(|event| event.line()).collect();
+        assert_eq!(lines, vec!["EVENT seat VIP ZonaB 2 Reserved\n".to_string()]);
+        let lines: Vec<String> = zone_c.try_iter().map(|event| event.line()).collect();
+        assert_eq!(lines, vec!["EVENT seat General ZonaC 1 Reserved\n".to_string()]);
+    }
+
+    #[test]
+    fn offers_reach_only_their_holder() {
+        let mut notifier = Notifier::default();
+        let first = notifier.subscribe(1, filter("General"));
+        let second = notifier.subscribe(2, Filter::default());
+        let offer = Offer { holder: 1, typ: "VIP".to_string(), seats: vec![("ZonaA".to_string(), 2)], expires_at: Instant::now() };
+        notifier.offer(&offer, 60);
+        let lines: Vec<String> = first.try_iter().map(|event| event.line()).collect();
+        assert_eq!(lines, vec!["EVENT offer VIP (ZonaA,2) claim within 60s\n".to_string()]);
+        assert!(second.try_recv().is_err());
+    }
+
+    #[test]
+    fn closed_and_replaced_subscriptions_stop_receiving() {
+        let mut notifier = Notifier::default();
+        let old = notifier.subscribe(1, Filter::default());
+        let new = notifier.subscribe(1, filter("VIP"));
+        notifier.seat_changed("VIP", "ZonaA", 2, &SeatStatus::Free);
+        assert!(old.try_recv().is_err());
+        assert!(new.try_recv().is_ok());
+
+        // Un receptor cerrado se descarta en la siguiente publicación
+        drop(new);
+        notifier.seat_changed("VIP", "ZonaA", 2, &SeatStatus::Free);
+        assert!(!notifier.has_subscribers());
+        assert!(!notifier.unsubscribe(1));
+    }
+}
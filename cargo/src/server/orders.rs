@@ -0,0 +1,283 @@
+use super::error::TicketingError;
+use super::tickets::{self, TicketClaims};
+use super::{SeatStatus, SeatingStructure};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug)]
+pub struct Refund {
+    /**
+    Reembolso de una compra:
+    - `amount`: Monto devuelto.
+    - `refunded_at`: Momento del reembolso (segundos unix).
+    */
+    pub amount: u32,
+    pub refunded_at: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Order {
+    /**
+    Registro de la compra de un asiento:
+    - `id`: Número de la orden.
+    - `ticket_id`: Identificador único del boleto emitido.
+    - `holder`: Conexión que hizo la compra.
+    - `typ`, `zone`, `number`: Asiento comprado.
+    - `price`: Precio pagado.
+    - `purchased_at`: Momento de la compra (segundos unix).
+    - `refund`: Reembolso de la compra, si lo hubo.
+    - `checked_in_at`: Momento en que se escaneó el boleto en la entrada.
+    */
+    pub id: u64,
+    pub ticket_id: String,
+    pub holder: Option<u64>,
+    pub typ: String,
+    pub zone: String,
+    pub number: u32,
+    pub price: u32,
+    pub purchased_at: u64,
+    pub refund: Option<Refund>,
+    pub checked_in_at: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RefundPolicy {
+    /**
+    Política de reembolsos del evento:
+    - `enabled`: Si se permiten reembolsos.
+    - `percentage`: Porcentaje del precio que se devuelve.
+    - `deadline`: Fecha límite para pedir el reembolso (segundos unix), `None` sin límite.
+    */
+    pub enabled: bool,
+    pub percentage: u8,
+    pub deadline: Option<u64>,
+}
+
+impl Default for RefundPolicy {
+    fn default() -> Self {
+        RefundPolicy { enabled: true, percentage: 100, deadline: None }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    /**
+    Compras registradas y la configuración de venta del evento:
+    - `orders`: Compras registradas.
+    - `refund_policy`: Política de reembolso que se les aplica.
+    - `event`: Nombre del evento, va en cada boleto.
+    - `ticket_secret`: Clave con la que se firman los boletos.
+    */
+    pub orders: Vec<Order>,
+    pub refund_policy: RefundPolicy,
+    pub event: String,
+    pub ticket_secret: Vec<u8>,
+}
+
+pub fn now() -> u64 {
+    // Segundos unix actuales
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+impl OrderBook {
+    pub fn record(&mut self, holder: u64, typ: &str, zone: &str, number: u32, price: u32) -> u64 {
+        /**
+        Registra la compra de un asiento y emite su boleto.
+        Retorna el número de orden.
+        */
+        let id = self.orders.last().map_or(1, |order| order.id + 1);
+        self.orders.push(Order {
+            id,
+            ticket_id: tickets::new_ticket_id(id),
+            holder: Some(holder),
+            typ: typ.to_string(),
+            zone: zone.to_string(),
+            number,
+            price,
+            purchased_at: now(),
+            refund: None,
+            checked_in_at: None,
+        });
+        id
+    }
+
+    pub fn ticket_code(&self, order: &Order) -> String {
+        // Código firmado del boleto de una orden
+        tickets::issue_code(&self.ticket_secret, &TicketClaims::from_order(order, &self.event))
+    }
+
+    pub fn order(&self, id: u64) -> Option<&Order> {
+        self.orders.iter().find(|order| order.id == id)
+    }
+
+    pub fn order_mut(&mut self, id: u64) -> Option<&mut Order> {
+        self.orders.iter_mut().find(|order| order.id == id)
+    }
+
+    pub fn seat_order_id(&self, typ: &str, zone: &str, number: u32) -> Option<u64> {
+        // La orden vigente (sin reembolso) de un asiento
+        self.orders
+            .iter()
+            .rev()
+            .find(|order| order.typ == typ && order.zone == zone && order.number == number && order.refund.is_none())
+            .map(|order| order.id)
+    }
+
+    pub fn refund_amount(&self, order: &Order, at: u64) -> Result<u32, TicketingError> {
+        /**
+        Calcula cuánto se devuelve de una orden según la política de reembolso.
+        Retorna un error si la política no permite el reembolso.
+        */
+        let policy = &self.refund_policy;
+        if !policy.enabled {
+            return Err(TicketingError::RefundsDisabled);
+        }
+        if policy.deadline.is_some_and(|deadline| at > deadline) {
+            return Err(TicketingError::RefundDeadlinePassed);
+        }
+        if order.refund.is_some() {
+            return Err(TicketingError::AlreadyRefunded(order.id));
+        }
+        // En u64 para que un precio alto no desborde, con el porcentaje hasta 100 el resultado cabe en u32
+        Ok((order.price as u64 * policy.percentage as u64 / 100) as u32)
+    }
+}
+
+impl SeatingStructure {
+    pub fn check_in(&mut self, code: &str, at: u64) -> Result<Order, TicketingError> {
+        /**
+        Valida un boleto en la entrada y lo marca como escaneado.
+
+        Rechaza boletos con firma inválida, de otro evento, desconocidos,
+        que no coinciden con su orden, reembolsados, ya escaneados o cuyo
+        asiento ya no está comprado por esa misma orden (liberado o revendido).
+        */
+        let orders = &self.orders;
+        let claims = tickets::verify_code(&orders.ticket_secret, code).map_err(TicketingError::ForgedTicket)?;
+        if claims.event != orders.event {
+            return Err(TicketingError::WrongEvent(claims.event));
+        }
+        let order = orders
+            .orders
+            .iter()
+            .find(|order| order.ticket_id == claims.ticket_id)
+            .ok_or_else(|| TicketingError::UnknownTicket(claims.ticket_id.clone()))?;
+        if TicketClaims::from_order(order, &orders.event) != claims {
+            return Err(TicketingError::ForgedTicket(format!("{} does not match its order", claims.ticket_id)));
+        }
+        if order.refund.is_some() {
+            return Err(TicketingError::TicketRefunded(order.ticket_id.clone()));
+        }
+        if let Some(checked_in_at) = order.checked_in_at {
+            return Err(TicketingError::AlreadyCheckedIn(order.ticket_id.clone(), checked_in_at));
+        }
+
+        let (order_id, ticket_id) = (order.id, order.ticket_id.clone());
+        let (typ, zone, number) = (order.typ.clone(), order.zone.clone(), order.number);
+        let live_order = orders.seat_order_id(&typ, &zone, number) == Some(order_id);
+        let purchased = self.seat(&typ, &zone, number).is_some_and(|seat| seat.status == SeatStatus::Purchased);
+        if !live_order || !purchased {
+            return Err(TicketingError::TicketVoid(ticket_id));
+        }
+        let order = self.orders.order_mut(order_id).ok_or(TicketingError::UnknownTicket(ticket_id))?;
+        order.checked_in_at = Some(at);
+        Ok(order.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sold_seat() -> (SeatingStructure, u64) {
+        // Estructura con el boleto VIP ZonaB 1 vendido a la conexión 1
+        let mut seating_structure = SeatingStructure::new();
+        seating_structure.orders.event = "concierto".to_string();
+        seating_structure.orders.ticket_secret = b"secreto".to_vec();
+        let order_id = seating_structure.purchase_seat("VIP", "ZonaB", 1, 1).unwrap();
+        (seating_structure, order_id)
+    }
+
+    fn ticket_code(seating_structure: &SeatingStructure, order_id: u64) -> String {
+        let orders = &seating_structure.orders;
+        orders.ticket_code(orders.order(order_id).unwrap())
+    }
+
+    #[test]
+    fn refund_amount_follows_the_policy() {
+        let mut orders = OrderBook::default();
+        let order_id = orders.record(1, "VIP", "ZonaA", 2, 150);
+        let order = orders.order(order_id).unwrap().clone();
+        assert_eq!(orders.refund_amount(&order, 0), Ok(150));
+
+        orders.refund_policy = RefundPolicy { enabled: true, percentage: 50, deadline: Some(100) };
+        assert_eq!(orders.refund_amount(&order, 100), Ok(75));
+        assert_eq!(orders.refund_amount(&order, 101), Err(TicketingError::RefundDeadlinePassed));
+
+        orders.refund_policy.enabled = false;
+        assert_eq!(orders.refund_amount(&order, 0), Err(TicketingError::RefundsDisabled));
+    }
+
+    #[test]
+    fn refund_amount_does_not_overflow_on_high_prices() {
+        let mut orders = OrderBook::default();
+        let order_id = orders.record(1, "VIP", "ZonaA", 2, u32::MAX);
+        let order = orders.order(order_id).unwrap().clone();
+        assert_eq!(orders.refund_amount(&order, 0), Ok(u32::MAX));
+        orders.refund_policy.percentage = 50;
+        assert_eq!(orders.refund_amount(&order, 0), Ok(u32::MAX / 2));
+    }
+
+    #[test]
+    fn refund_order_frees_the_seat_once() {
+        let (mut seating_structure, order_id) = sold_seat();
+        assert_eq!(seating_structure.refund_order(order_id, 2), Err(TicketingError::OrderNotOwned(order_id)));
+        assert_eq!(seating_structure.refund_order(order_id, 1), Ok(120));
+        assert_eq!(seating_structure.seat("VIP", "ZonaB", 1).map(|seat| seat.status.clone()), Some(SeatStatus::Free));
+        assert_eq!(seating_structure.refund_order(order_id, 1), Err(TicketingError::AlreadyRefunded(order_id)));
+    }
+
+    #[test]
+    fn check_in_accepts_a_ticket_once() {
+        let (mut seating_structure, order_id) = sold_seat();
+        let code = ticket_code(&seating_structure, order_id);
+        let order = seating_structure.check_in(&code, 10).unwrap();
+        assert_eq!((order.id, order.checked_in_at), (order_id, Some(10)));
+        assert_eq!(seating_structure.check_in(&code, 11).err(), Some(TicketingError::AlreadyCheckedIn(order.ticket_id, 10)));
+    }
+
+    #[test]
+    fn check_in_rejects_refunded_and_void_tickets() {
+        let (mut seating_structure, order_id) = sold_seat();
+        let code = ticket_code(&seating_structure, order_id);
+        let ticket_id = seating_structure.orders.order(order_id).unwrap().ticket_id.clone();
+        seating_structure.refund_order(order_id, 1).unwrap();
+        assert_eq!(seating_structure.check_in(&code, 10).err(), Some(TicketingError::TicketRefunded(ticket_id)));
+
+        // Un asiento que ya no está comprado deja sin valor a su boleto
+        let (mut seating_structure, order_id) = sold_seat();
+        let code = ticket_code(&seating_structure, order_id);
+        let ticket_id = seating_structure.orders.order(order_id).unwrap().ticket_id.clone();
+        seating_structure.seat_mut("VIP", "ZonaB", 1).unwrap().status = SeatStatus::Free;
+        assert_eq!(seating_structure.check_in(&code, 10).err(), Some(TicketingError::TicketVoid(ticket_id)));
+    }
+
+    #[test]
+    fn check_in_rejects_forged_and_foreign_tickets() {
+        let (mut seating_structure, order_id) = sold_seat();
+        let code = ticket_code(&seating_structure, order_id);
+        let tampered = code.replacen(".120.", ".1.", 1);
+        assert!(matches!(seating_structure.check_in(&tampered, 10), Err(TicketingError::ForgedTicket(_))));
+
+        let mut claims = TicketClaims::from_order(seating_structure.orders.order(order_id).unwrap(), "otro");
+        let foreign = tickets::issue_code(b"secreto", &claims);
+        assert_eq!(seating_structure.check_in(&foreign, 10).err(), Some(TicketingError::WrongEvent("otro".to_string())));
+
+        claims.event = "concierto".to_string();
+        claims.ticket_id = "T99-00000000".to_string();
+        let unknown = tickets::issue_code(b"secreto", &claims);
+        assert_eq!(seating_structure.check_in(&unknown, 10).err(), Some(TicketingError::UnknownTicket("T99-00000000".to_string())));
+    }
+}
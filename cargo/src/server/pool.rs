@@ -0,0 +1,120 @@
+use super::logging;
+use serde_json::json;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+pub struct ThreadPool {
+    /**
+    Grupo fijo de hilos que atienden las conexiones.
+    Los trabajos se reparten por un canal compartido entre los hilos.
+    */
+    workers: Vec<Worker>,
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+struct Worker {
+    id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    pub fn new(size: usize) -> Self {
+        /**
+        Crea el grupo con `size` hilos, `size` debe ser mayor que cero
+        */
+        assert!(size > 0, "Thread pool needs at least one worker");
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..size)
+            .map(|id| Worker::new(id, Arc::clone(&receiver)))
+            .collect();
+
+        ThreadPool { workers, sender: Some(sender) }
+    }
+
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // Encola el trabajo, lo toma el primer hilo libre
+        if let Some(sender) = &self.sender {
+            if sender.send(Box::new(job)).is_err() {
+                logging::warn("Thread pool is shut down, dropping job", json!({}));
+            }
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // Cerrar el canal hace que cada hilo termine después de su trabajo actual
+        drop(self.sender.take());
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                if thread.join().is_err() {
+                    logging::error("Worker panicked", json!({ "worker": worker.id }));
+                }
+            }
+        }
+    }
+}
+
+impl Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Self {
+        let thread = thread::spawn(move || loop {
+            let job = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => break,
+            };
+            match job {
+                // Un pánico en un trabajo no mata al hilo, sigue atendiendo los siguientes
+                Ok(job) => {
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        logging::error("Job panicked, worker keeps running", json!({ "worker": id }));
+                    }
+                }
+                // El canal se cerró, no hay más trabajos
+                Err(_) => break,
+            }
+        });
+        Worker { id, thread: Some(thread) }
+    }
+}
+
+pub struct ConnectionSlot {
+    /**
+    Lugar ocupado por una conexión activa, se libera al salir de alcance
+    */
+    active: Arc<AtomicUsize>,
+}
+
+impl ConnectionSlot {
+    pub fn acquire(active: &Arc<AtomicUsize>, max_connections: usize) -> Option<Self> {
+        /**
+        Ocupa un lugar si hay menos de `max_connections` conexiones activas.
+        Retorna `None` si el servidor está lleno.
+        */
+        let mut current = active.load(Ordering::SeqCst);
+        loop {
+            if current >= max_connections {
+                return None;
+            }
+            match active.compare_exchange(current, current + 1, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return Some(ConnectionSlot { active: Arc::clone(active) }),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,155 @@
+use super::{Seat, SeatStatus, SeatingStructure, END_MARKER};
+
+const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MapOptions {
+    /**
+    Opciones del mapa de asientos:
+    - `vision`: Muestra el porcentaje de visión de cada asiento.
+    - `plain`: Sin colores, el estado se muestra con una letra.
+    - `features`: Marca los atributos de accesibilidad de cada asiento.
+    */
+    pub vision: bool,
+    pub plain: bool,
+    pub features: bool,
+}
+
+impl MapOptions {
+    pub fn from_args<'a>(args: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        // Argumentos del comando `map`: cualquier mezcla de vision, plain y features
+        let mut options = MapOptions::default();
+        for arg in args {
+            match arg {
+                "vision" => options.vision = true,
+                "plain" => options.plain = true,
+                "features" => options.features = true,
+                _ => return Err(format!("Invalid map option: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn color(status: &SeatStatus) -> &'static str {
+    match status {
+        SeatStatus::Free => "\x1b[42;30m",
+        SeatStatus::Reserved => "\x1b[43;30m",
+        SeatStatus::Purchased => "\x1b[41;97m",
+        SeatStatus::Blocked => "\x1b[100;97m",
+    }
+}
+
+fn letter(status: &SeatStatus) -> char {
+    match status {
+        SeatStatus::Free => 'F',
+        SeatStatus::Reserved => 'R',
+        SeatStatus::Purchased => 'P',
+        SeatStatus::Blocked => 'B',
+    }
+}
+
+// Ninguna letra se repite con las de `letter`, en el mapa `plain` van en la misma celda
+const FEATURE_MARKS: [(char, &str); 5] =
+    [('W', "wheelchair"), ('C', "companion"), ('A', "aisle"), ('V', "restricted-view"), ('E', "near-exit")];
+
+fn feature_marks(seat: &Seat) -> String {
+    // Una letra por atributo del asiento, `-` si no tiene
+    let names = seat.features.names();
+    let marks: String = FEATURE_MARKS.iter().filter(|(_, name)| names.contains(name)).map(|(mark, _)| *mark).collect();
+    if marks.is_empty() {
+        "-".to_string()
+    } else {
+        marks
+    }
+}
+
+fn render_seat(seat: &Seat, options: MapOptions) -> String {
+    // Una celda de la cuadrícula, por ejemplo `[ 3]`, `[ 3 95%]` o `[ 3 WA]`
+    let mut label = format!("{:>2}", seat.number);
+    if options.vision {
+        label.push_str(&format!(" {:>3}%", seat.vision_percentage));
+    }
+    if options.features {
+        label.push_str(&format!(" {}", feature_marks(seat)));
+    }
+    if options.plain {
+        format!("[{} {}]", label, letter(&seat.status))
+    } else {
+        format!("{}[{}]{}", color(&seat.status), label, RESET)
+    }
+}
+
+fn legend(options: MapOptions) -> String {
+    let statuses = [
+        (SeatStatus::Free, "free"),
+        (SeatStatus::Reserved, "reserved"),
+        (SeatStatus::Purchased, "purchased"),
+        (SeatStatus::Blocked, "blocked"),
+    ];
+    statuses
+        .iter()
+        .map(|(status, name)| {
+            if options.plain {
+                format!("{}={}", letter(status), name)
+            } else {
+                format!("{}  {} {}", color(status), RESET, name)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("  ")
+}
+
+pub fn render_seat_map(seating_structure: &SeatingStructure, options: MapOptions) -> String {
+    /**
+    Dibuja la cuadrícula de asientos de cada zona, una fila para VIP y otra
+    para General, con un color por estado del asiento.
+
+    El texto termina con la línea `END_MARKER`.
+    */
+    let mut map = format!("Seat map  {}\n", legend(options));
+    if options.features {
+        let marks: Vec<String> = FEATURE_MARKS.iter().map(|(mark, name)| format!("{}={}", mark, name)).collect();
+        map.push_str(&format!("Features  {}\n", marks.join("  ")));
+    }
+    for (index, category) in seating_structure.categories.iter().enumerate() {
+        if seating_structure.categories.len() > 1 {
+            map.push_str(&format!("Category {}\n", index + 1));
+        }
+        for zone in &category.zones {
+            map.push_str(&format!("{}\n", zone.name));
+            for (typ, seats) in [("VIP", &zone.Vip.seats), ("General", &zone.General.seats)] {
+                let row: String = seats.iter().map(|seat| render_seat(seat, options)).collect();
+                let closed = if seating_structure.sales_open(typ) { "" } else { "  (sales closed)" };
+                map.push_str(&format!("  {:<8} {}{}\n", typ, row, closed));
+            }
+        }
+    }
+    map.push_str(END_MARKER);
+    map.push('\n');
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_marks_never_reuse_status_letters() {
+        let statuses = [SeatStatus::Free, SeatStatus::Reserved, SeatStatus::Purchased, SeatStatus::Blocked];
+        for (mark, name) in FEATURE_MARKS {
+            assert!(statuses.iter().all(|status| letter(status) != mark), "{} clashes with a status", name);
+        }
+    }
+
+    #[test]
+    fn plain_map_shows_features_next_to_the_status() {
+        let options = MapOptions { vision: false, plain: true, features: true };
+        let map = render_seat_map(&SeatingStructure::new(), options);
+        assert!(map.contains("Features  W=wheelchair  C=companion  A=aisle  V=restricted-view  E=near-exit\n"));
+        // VIP ZonaA 5 está reservado y en el pasillo, General ZonaB 1 es un espacio con vista limitada
+        assert!(map.contains("[ 5 A R]"));
+        assert!(map.contains("[ 1 WAV F]"));
+        assert!(map.ends_with("END\n"));
+    }
+}
@@ -0,0 +1,467 @@
+use super::access::SeatFeatures;
+use super::error::TicketingError;
+use super::{format_seats, SeatStatus, SeatingStructure, MAX_SEATS_PER_REQUEST};
+use std::cmp::Reverse;
+
+// Reparto entre dos zonas con su puntaje (parte más grande, distancia, visión) y sus asientos
+type ScoredSplit = ((usize, u32, u32), Vec<(String, u32)>);
+
+#[derive(Clone, Debug, Default)]
+pub struct FindConstraints {
+    /**
+    Restricciones de una búsqueda de asientos:
+    - `same_zone`: Todos los asientos en la misma zona. Cada zona tiene una sola fila
+      por tipo, así que también es la misma fila.
+    - `together`: Asientos contiguos (números seguidos) en la misma zona.
+    - `min_vision`, `max_vision`: Rango de visión aceptado para cada asiento.
+    - `zones`: Solo estas zonas, vacío para todas.
+    - `exclude`: Zonas que no se aceptan.
+    - `wheelchair`: Cantidad de espacios para silla de ruedas en el grupo, cada uno con
+      su acompañante contiguo. Sin pedirlos, esos espacios no se ofrecen.
+    - `aisle`: Al menos un asiento de pasillo en el grupo.
+    - `near_exit`: Todos los asientos cerca de una salida.
+    - `unrestricted`: Ningún asiento con la vista tapada.
+    */
+    pub same_zone: bool,
+    pub together: bool,
+    pub min_vision: Option<u8>,
+    pub max_vision: Option<u8>,
+    pub zones: Vec<String>,
+    pub exclude: Vec<String>,
+    pub wheelchair: usize,
+    pub aisle: bool,
+    pub near_exit: bool,
+    pub unrestricted: bool,
+}
+
+fn parse_vision(value: &str) -> Result<u8, TicketingError> {
+    match value.parse::<u8>() {
+        Ok(vision) if vision <= 100 => Ok(vision),
+        _ => Err(TicketingError::InvalidArgument(format!("Invalid vision percentage: {}", value))),
+    }
+}
+
+impl FindConstraints {
+    pub fn from_args<'a>(args: impl Iterator<Item = &'a str>) -> Result<Self, TicketingError> {
+        /**
+        Lee las restricciones del comando `find`, por ejemplo
+        `find VIP 4 same-zone min-vision=90 exclude=ZonaC`.
+
+        Opciones: `same-zone` (o `same-row`), `together`, `min-vision=<n>`,
+        `max-vision=<n>`, `zones=<zona,...>`, `exclude=<zona,...>`, `wheelchair=<n>`,
+        `aisle`, `near-exit`, `unrestricted`.
+        */
+        let mut constraints = FindConstraints::default();
+        for arg in args {
+            match arg.split_once('=') {
+                None if arg == "same-zone" || arg == "same-row" => constraints.same_zone = true,
+                None if arg == "together" => constraints.together = true,
+                Some(("min-vision", value)) => constraints.min_vision = Some(parse_vision(value)?),
+                Some(("max-vision", value)) => constraints.max_vision = Some(parse_vision(value)?),
+                Some(("zones", value)) => constraints.zones = value.split(',').map(str::to_string).collect(),
+                Some(("exclude", value)) => constraints.exclude = value.split(',').map(str::to_string).collect(),
+                None if arg == "aisle" => constraints.aisle = true,
+                None if arg == "near-exit" => constraints.near_exit = true,
+                None if arg == "unrestricted" => constraints.unrestricted = true,
+                Some(("wheelchair", value)) => {
+                    constraints.wheelchair = value
+                        .parse()
+                        .map_err(|_| TicketingError::InvalidArgument(format!("Invalid wheelchair count: {}", value)))?
+                }
+                _ => return Err(TicketingError::InvalidArgument(format!("Invalid find option: {}", arg))),
+            }
+        }
+        if constraints.min_vision.zip(constraints.max_vision).is_some_and(|(min, max)| min > max) {
+            return Err(TicketingError::InvalidArgument("min-vision can't be greater than max-vision.".to_string()));
+        }
+        if constraints.wheelchair > 0 && constraints.wheelchair * 2 > MAX_SEATS_PER_REQUEST {
+            return Err(TicketingError::InvalidArgument(format!(
+                "At most {} wheelchair spaces fit in one request with their companions.",
+                MAX_SEATS_PER_REQUEST / 2
+            )));
+        }
+        Ok(constraints)
+    }
+
+    fn accepts_zone(&self, zone: &str) -> bool {
+        (self.zones.is_empty() || self.zones.iter().any(|name| name == zone)) && !self.exclude.iter().any(|name| name == zone)
+    }
+
+    fn accepts_vision(&self, vision: u8) -> bool {
+        self.min_vision.is_none_or(|min| vision >= min) && self.max_vision.is_none_or(|max| vision <= max)
+    }
+
+    fn accepts_seat(&self, features: &SeatFeatures) -> bool {
+        // Los espacios para silla de ruedas solo se ofrecen si se pidieron
+        (self.wheelchair > 0 || !features.wheelchair)
+            && (!self.near_exit || features.near_exit)
+            && (!self.unrestricted || !features.restricted_view)
+    }
+
+    fn accepts_group(&self, seats: &[Candidate]) -> bool {
+        /**
+        Revisa las restricciones del grupo completo: que esté junto si se pidió,
+        que tenga un asiento de pasillo si se pidió, y que cada espacio para silla
+        de ruedas tenga un acompañante contiguo dentro del grupo.
+        */
+        let spread = seats[seats.len() - 1].number - seats[0].number;
+        let wheelchairs: Vec<&Candidate> = seats.iter().filter(|seat| seat.features.wheelchair).collect();
+        (!self.together || spread as usize == seats.len() - 1)
+            && (!self.aisle || seats.iter().any(|seat| seat.features.aisle))
+            && wheelchairs.len() == self.wheelchair
+            && wheelchairs.iter().all(|wheelchair| {
+                seats
+                    .iter()
+                    .any(|seat| seat.features.companion && seat.number.abs_diff(wheelchair.number) == 1)
+            })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlternativeKind {
+    /**
+    Tipo de alternativa cuando una búsqueda no se puede cumplir tal cual:
+    - Split: La misma cantidad y tipo, repartida entre dos zonas.
+    - OtherCategory: La misma cantidad en el otro tipo de asiento.
+    - LargestBlock: El grupo contiguo más grande que sí hay del mismo tipo.
+    */
+    Split,
+    OtherCategory,
+    LargestBlock,
+}
+
+impl AlternativeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlternativeKind::Split => "split",
+            AlternativeKind::OtherCategory => "other-category",
+            AlternativeKind::LargestBlock => "largest-block",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Alternative {
+    pub kind: AlternativeKind,
+    pub typ: String,
+    pub seats: Vec<(String, u32)>,
+}
+
+impl Alternative {
+    pub fn line(&self, rank: usize) -> String {
+        // Formato del protocolo TCP, por ejemplo `1. split VIP (ZonaA,4) (ZonaB,1)`
+        format!("{}. {} {} {}\n", rank, self.kind.name(), self.typ, format_seats(&self.seats))
+    }
+}
+
+struct Candidate {
+    zone: String,
+    number: u32,
+    vision: u32,
+    features: SeatFeatures,
+}
+
+fn best_window(seats: &[Candidate], seat_count: usize, constraints: &FindConstraints) -> Option<(u32, u32, usize)> {
+    /**
+    Mejor grupo de asientos seguidos en la lista (ordenada por número) de una zona:
+    el de menor distancia entre el primero y el último, y a igual distancia el de más visión.
+    Retorna (distancia, visión total, índice del primer asiento).
+    */
+    seats
+        .windows(seat_count)
+        .enumerate()
+        .map(|(start, window)| {
+            let spread = window[seat_count - 1].number - window[0].number;
+            let vision = window.iter().map(|seat| seat.vision).sum::<u32>();
+            (spread, vision, start)
+        })
+        .filter(|(_, _, start)| constraints.accepts_group(&seats[*start..*start + seat_count]))
+        .min_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+}
+
+fn window_seats(window: &[Candidate]) -> Vec<(String, u32)> {
+    // Los acompañantes van antes que su espacio para silla de ruedas
+    let mut seats: Vec<&Candidate> = window.iter().collect();
+    seats.sort_by_key(|seat| seat.features.wheelchair);
+    seats.iter().map(|seat| (seat.zone.clone(), seat.number)).collect()
+}
+
+impl SeatingStructure {
+    fn candidates(&self, typ: &str, constraints: &FindConstraints) -> Vec<Vec<Candidate>> {
+        // Asientos libres de cada zona aceptada que cumplen las restricciones por asiento, ordenados por número
+        self.categories
+            .iter()
+            .flat_map(|category| category.zones.iter())
+            .filter(|zone| constraints.accepts_zone(&zone.name))
+            .map(|zone| {
+                let seats = if typ == "VIP" { &zone.Vip.seats } else { &zone.General.seats };
+                let mut candidates: Vec<Candidate> = seats
+                    .iter()
+                    .filter(|seat| {
+                        seat.status == SeatStatus::Free
+                            && constraints.accepts_vision(seat.vision_percentage)
+                            && constraints.accepts_seat(&seat.features)
+                    })
+                    .map(|seat| Candidate {
+                        zone: zone.name.clone(),
+                        number: seat.number,
+                        vision: seat.vision_percentage as u32,
+                        features: seat.features,
+                    })
+                    .collect();
+                candidates.sort_by_key(|seat| seat.number);
+                candidates
+            })
+            .collect()
+    }
+
+    pub fn find_seats(
+        &self,
+        typ: &str,
+        seat_count: u32,
+        constraints: &FindConstraints,
+    ) -> Result<Vec<(String, u32)>, TicketingError> {
+        /**
+        Busca asientos libres de un tipo que cumplan las restricciones.
+
+        Prefiere los asientos de una misma zona lo más juntos posible y, entre
+        ellos, los de mejor visión. Si ninguna zona alcanza y no se pidió
+        `same_zone`, `together`, `aisle` ni espacios para silla de ruedas, toma
+        los de mejor visión entre varias zonas.
+
+        Los acompañantes van antes que su espacio para silla de ruedas, en el
+        orden en que se deben reservar uno por uno.
+
+        Retorna una lista vacía si no hay asientos que cumplan.
+        */
+        if typ != "VIP" && typ != "General" {
+            return Err(TicketingError::InvalidCategory(typ.to_string()));
+        }
+        let seat_count = seat_count as usize;
+        if seat_count == 0 || seat_count > MAX_SEATS_PER_REQUEST {
+            return Err(TicketingError::InvalidSeatCount);
+        }
+
+        let zones = self.candidates(typ, constraints);
+
+        // Mejor grupo dentro de una sola zona, a igualdad gana la primera zona
+        let best = zones
+            .iter()
+            .enumerate()
+            .filter_map(|(index, seats)| best_window(seats, seat_count, constraints).map(|best| (best, index)))
+            .min_by(|((spread_a, vision_a, _), zone_a), ((spread_b, vision_b, _), zone_b)| {
+                spread_a.cmp(spread_b).then(vision_b.cmp(vision_a)).then(zone_a.cmp(zone_b))
+            });
+        if let Some(((_, _, start), index)) = best {
+            return Ok(window_seats(&zones[index][start..start + seat_count]));
+        }
+        if constraints.same_zone || constraints.together || constraints.aisle || constraints.wheelchair > 0 {
+            return Ok(vec![]);
+        }
+
+        // Ninguna zona alcanza sola: los de mejor visión entre todas las zonas
+        let mut seats: Vec<&Candidate> = zones.iter().flatten().collect();
+        if seats.len() < seat_count {
+            return Ok(vec![]);
+        }
+        seats.sort_by_key(|seat| Reverse(seat.vision));
+        let mut chosen: Vec<(String, u32)> = seats[..seat_count].iter().map(|seat| (seat.zone.clone(), seat.number)).collect();
+        chosen.sort();
+        Ok(chosen)
+    }
+
+    fn split_seats(&self, typ: &str, seat_count: usize, constraints: &FindConstraints) -> Option<Vec<(String, u32)>> {
+        /**
+        Reparte el grupo entre dos zonas, cada parte lo más junta posible.
+        Las restricciones de grupo (`aisle`, silla de ruedas) se piden a la primera
+        parte y `together` a cada parte por separado.
+
+        Prefiere la parte más grande en una sola zona, después la menor distancia
+        entre asientos y después la mejor visión.
+        */
+        let zones = self.candidates(typ, constraints);
+        let rest = FindConstraints { aisle: false, wheelchair: 0, ..constraints.clone() };
+        let mut best: Option<ScoredSplit> = None;
+        for (first_index, first) in zones.iter().enumerate() {
+            for (second_index, second) in zones.iter().enumerate() {
+                if first_index == second_index {
+                    continue;
+                }
+                for first_count in 1..seat_count {
+                    let second_count = seat_count - first_count;
+                    let (Some((spread_a, vision_a, start_a)), Some((spread_b, vision_b, start_b))) = (
+                        best_window(first, first_count, constraints),
+                        best_window(second, second_count, &rest),
+                    ) else {
+                        continue;
+                    };
+                    let score = (first_count.max(second_count), spread_a + spread_b, vision_a + vision_b);
+                    let better = best.as_ref().is_none_or(|((largest, spread, vision), _)| {
+                        score.0.cmp(largest).then(spread.cmp(&score.1)).then(score.2.cmp(vision)).is_gt()
+                    });
+                    if better {
+                        let mut seats = window_seats(&first[start_a..start_a + first_count]);
+                        seats.extend(window_seats(&second[start_b..start_b + second_count]));
+                        best = Some((score, seats));
+                    }
+                }
+            }
+        }
+        best.map(|(_, seats)| seats)
+    }
+
+    pub fn suggest_alternatives(&self, typ: &str, seat_count: u32, constraints: &FindConstraints) -> Vec<Alternative> {
+        /**
+        Alternativas para una búsqueda que no encontró asientos, ordenadas de la
+        más parecida a la menos: el grupo repartido entre dos zonas, la misma
+        cantidad en el otro tipo (si su venta está abierta) y el grupo contiguo
+        más grande que queda del mismo tipo.
+        */
+        let mut alternatives = Vec::new();
+        if (typ != "VIP" && typ != "General") || seat_count == 0 || seat_count as usize > MAX_SEATS_PER_REQUEST {
+            return alternatives;
+        }
+        if let Some(seats) = self.split_seats(typ, seat_count as usize, constraints) {
+            alternatives.push(Alternative { kind: AlternativeKind::Split, typ: typ.to_string(), seats });
+        }
+
+        let other = if typ == "VIP" { "General" } else { "VIP" };
+        if self.sales_open(other) {
+            if let Ok(seats) = self.find_seats(other, seat_count, constraints) {
+                if !seats.is_empty() {
+                    alternatives.push(Alternative { kind: AlternativeKind::OtherCategory, typ: other.to_string(), seats });
+                }
+            }
+        }
+
+        let together = FindConstraints { together: true, ..constraints.clone() };
+        let block = (1..seat_count)
+            .rev()
+            .find_map(|count| self.find_seats(typ, count, &together).ok().filter(|seats| !seats.is_empty()));
+        if let Some(seats) = block {
+            alternatives.push(Alternative { kind: AlternativeKind::LargestBlock, typ: typ.to_string(), seats });
+        }
+        alternatives
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seats(expected: &[(&str, u32)]) -> Vec<(String, u32)> {
+        expected.iter().map(|(zone, number)| (zone.to_string(), *number)).collect()
+    }
+
+    fn constraints(args: &str) -> FindConstraints {
+        FindConstraints::from_args(args.split_whitespace()).unwrap()
+    }
+
+    #[test]
+    fn find_seats_prefers_one_zone_together_with_best_vision() {
+        let seating_structure = SeatingStructure::new();
+        assert_eq!(seating_structure.find_seats("VIP", 2, &constraints("")), Ok(seats(&[("ZonaA", 2), ("ZonaA", 3)])));
+        assert_eq!(
+            seating_structure.find_seats("VIP", 3, &constraints("")),
+            Ok(seats(&[("ZonaB", 1), ("ZonaB", 2), ("ZonaB", 3)]))
+        );
+    }
+
+    #[test]
+    fn find_seats_applies_seat_constraints() {
+        let seating_structure = SeatingStructure::new();
+        assert_eq!(
+            seating_structure.find_seats("VIP", 2, &constraints("near-exit")),
+            Ok(seats(&[("ZonaC", 1), ("ZonaC", 2)]))
+        );
+        assert_eq!(
+            seating_structure.find_seats("VIP", 2, &constraints("exclude=ZonaA max-vision=94")),
+            Ok(seats(&[("ZonaC", 1), ("ZonaC", 2)]))
+        );
+        assert_eq!(seating_structure.find_seats("General", 2, &constraints("zones=ZonaB unrestricted")), Ok(vec![]));
+    }
+
+    #[test]
+    fn find_seats_offers_wheelchair_spaces_only_when_asked() {
+        let seating_structure = SeatingStructure::new();
+        // Sin pedirlos, ninguna zona General completa cinco asientos
+        assert_eq!(seating_structure.find_seats("General", 5, &constraints("same-zone")), Ok(vec![]));
+        // El acompañante va antes que su espacio
+        assert_eq!(
+            seating_structure.find_seats("General", 2, &constraints("wheelchair=1")),
+            Ok(seats(&[("ZonaC", 4), ("ZonaC", 5)]))
+        );
+    }
+
+    #[test]
+    fn find_seats_mixes_zones_only_without_group_constraints() {
+        let seating_structure = SeatingStructure::new();
+        let found = seating_structure.find_seats("General", 5, &constraints("")).unwrap();
+        assert_eq!(found.len(), 5);
+        assert!(found.iter().any(|(zone, _)| zone != &found[0].0));
+        assert_eq!(seating_structure.find_seats("General", 5, &constraints("together")), Ok(vec![]));
+    }
+
+    #[test]
+    fn find_seats_validates_category_and_count() {
+        let seating_structure = SeatingStructure::new();
+        assert_eq!(
+            seating_structure.find_seats("Palco", 2, &constraints("")),
+            Err(TicketingError::InvalidCategory("Palco".to_string()))
+        );
+        assert_eq!(seating_structure.find_seats("VIP", 0, &constraints("")), Err(TicketingError::InvalidSeatCount));
+        assert_eq!(
+            seating_structure.find_seats("VIP", MAX_SEATS_PER_REQUEST as u32 + 1, &constraints("")),
+            Err(TicketingError::InvalidSeatCount)
+        );
+    }
+
+    #[test]
+    fn constraints_reject_invalid_options() {
+        for args in ["min-vision=101", "min-vision=90 max-vision=80", "wheelchair=3", "wheelchair=x", "sideways"] {
+            assert!(
+                matches!(FindConstraints::from_args(args.split_whitespace()), Err(TicketingError::InvalidArgument(_))),
+                "{}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn suggest_alternatives_ranks_split_other_category_and_largest_block() {
+        let seating_structure = SeatingStructure::new();
+        let lines: Vec<String> = seating_structure
+            .suggest_alternatives("General", 5, &constraints("together"))
+            .iter()
+            .enumerate()
+            .map(|(rank, alternative)| alternative.line(rank + 1))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "1. split General (ZonaA,1) (ZonaC,1) (ZonaC,2) (ZonaC,3) (ZonaC,4)\n",
+                "2. other-category VIP (ZonaB,1) (ZonaB,2) (ZonaB,3) (ZonaB,4) (ZonaB,5)\n",
+                "3. largest-block General (ZonaC,1) (ZonaC,2) (ZonaC,3) (ZonaC,4)\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn suggest_alternatives_skips_closed_categories_and_invalid_searches() {
+        let mut seating_structure = SeatingStructure::new();
+        seating_structure.closed_sales.push("VIP".to_string());
+        let kinds: Vec<AlternativeKind> = seating_structure
+            .suggest_alternatives("General", 5, &constraints("together"))
+            .iter()
+            .map(|alternative| alternative.kind)
+            .collect();
+        assert_eq!(kinds, vec![AlternativeKind::Split, AlternativeKind::LargestBlock]);
+
+        // Sin tipo válido o con una cantidad fuera de rango no hay nada que sugerir
+        assert!(seating_structure.suggest_alternatives("Palco", 2, &constraints("")).is_empty());
+        assert!(seating_structure.suggest_alternatives("General", 0, &constraints("")).is_empty());
+        assert!(seating_structure
+            .suggest_alternatives("General", MAX_SEATS_PER_REQUEST as u32 + 1, &constraints(""))
+            .is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use super::error::TicketingError;
+use super::{Seat, SeatStatus, SeatingStructure, END_MARKER};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Availability {
+    /**
+    Conteo de asientos de un grupo (tipo, zona o ambos):
+    - `total`, `free`, `reserved`, `purchased`, `blocked`: Asientos por estado.
+    - `free_vision`: Suma de la visión de los asientos libres, para el promedio.
+    */
+    pub total: u32,
+    pub free: u32,
+    pub reserved: u32,
+    pub purchased: u32,
+    pub blocked: u32,
+    pub free_vision: u32,
+}
+
+impl Availability {
+    fn add(&mut self, seat: &Seat) {
+        self.total += 1;
+        match seat.status {
+            SeatStatus::Free => {
+                self.free += 1;
+                self.free_vision += seat.vision_percentage as u32;
+            }
+            SeatStatus::Reserved => self.reserved += 1,
+            SeatStatus::Purchased => self.purchased += 1,
+            SeatStatus::Blocked => self.blocked += 1,
+        }
+    }
+
+    pub fn sell_through(&self) -> f64 {
+        // Porcentaje de asientos vendidos
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.purchased as f64 * 100.0 / self.total as f64
+    }
+
+    pub fn average_free_vision(&self) -> f64 {
+        // Visión promedio de los asientos que quedan libres
+        if self.free == 0 {
+            return 0.0;
+        }
+        self.free_vision as f64 / self.free as f64
+    }
+
+    fn format(&self, scope: &str) -> String {
+        format!(
+            "{} total={} free={} reserved={} purchased={} blocked={} sold={:.1}% avg_vision={:.1}\n",
+            scope,
+            self.total,
+            self.free,
+            self.reserved,
+            self.purchased,
+            self.blocked,
+            self.sell_through(),
+            self.average_free_vision()
+        )
+    }
+}
+
+pub fn availability(seating_structure: &SeatingStructure, typ: Option<&str>, zone_name: Option<&str>) -> Availability {
+    /**
+    Cuenta los asientos por estado, opcionalmente solo de un tipo y/o una zona
+    */
+    let mut availability = Availability::default();
+    for category in &seating_structure.categories {
+        for zone in &category.zones {
+            if zone_name.is_some_and(|name| name != zone.name) {
+                continue;
+            }
+            for (seat_typ, seats) in [("VIP", &zone.Vip.seats), ("General", &zone.General.seats)] {
+                if typ.is_some_and(|typ| typ != seat_typ) {
+                    continue;
+                }
+                seats.iter().for_each(|seat| availability.add(seat));
+            }
+        }
+    }
+    availability
+}
+
+pub fn format_stats(seating_structure: &SeatingStructure, filters: &[&str]) -> Result<String, TicketingError> {
+    /**
+    Arma la respuesta del comando `stats`, una línea por grupo:
+    total, por tipo, por zona y por tipo y zona.
+    Se puede filtrar por tipo (`stats VIP`), por zona (`stats ZonaB`) o por ambos (`stats VIP ZonaB`).
+
+    El texto termina con la línea `END_MARKER`, igual que el mapa de asientos.
+    */
+    // Un solo filtro es el tipo si es VIP o General, si no es una zona
+    let (typ, zone_name) = match filters {
+        [] => (None, None),
+        [typ] if *typ == "VIP" || *typ == "General" => (Some(*typ), None),
+        [zone_name] => (None, Some(*zone_name)),
+        [typ, zone_name] if *typ == "VIP" || *typ == "General" => (Some(*typ), Some(*zone_name)),
+        [typ, _] => return Err(TicketingError::InvalidCategory(typ.to_string())),
+        _ => return Err(TicketingError::InvalidArgument("Usage: stats [VIP|General] [zone]".to_string())),
+    };
+    let zones: Vec<&str> = seating_structure
+        .categories
+        .iter()
+        .flat_map(|category| category.zones.iter().map(|zone| zone.name.as_str()))
+        .filter(|name| zone_name.is_none_or(|zone_name| zone_name == *name))
+        .collect();
+    if zones.is_empty() {
+        return Err(TicketingError::InvalidArgument(format!("Invalid zone: {}", zone_name.unwrap_or(""))));
+    }
+    let types: Vec<&str> = ["VIP", "General"]
+        .into_iter()
+        .filter(|seat_typ| typ.is_none_or(|typ| typ == *seat_typ))
+        .collect();
+
+    let mut stats = availability(seating_structure, typ, zone_name).format("TOTAL");
+    if types.len() > 1 {
+        for seat_typ in &types {
+            stats.push_str(&availability(seating_structure, Some(seat_typ), zone_name).format(seat_typ));
+        }
+    }
+    if zones.len() > 1 {
+        for zone in &zones {
+            stats.push_str(&availability(seating_structure, typ, Some(zone)).format(zone));
+        }
+    }
+    if types.len() > 1 || zones.len() > 1 {
+        for seat_typ in &types {
+            for zone in &zones {
+                let scope = format!("{} {}", seat_typ, zone);
+                stats.push_str(&availability(seating_structure, Some(seat_typ), Some(zone)).format(&scope));
+            }
+        }
+    }
+    stats.push_str(END_MARKER);
+    stats.push('\n');
+    Ok(stats)
+}
+
+pub fn format_attendance(seating_structure: &SeatingStructure) -> String {
+    /**
+    Asistencia en vivo por tipo y zona: boletos escaneados contra boletos vigentes
+    (comprados y sin reembolso). Termina con la línea `END_MARKER`.
+    */
+    let mut groups: Vec<(String, String, u32, u32)> = Vec::new();
+    let (mut total_checked_in, mut total_valid) = (0, 0);
+    for order in seating_structure.orders.orders.iter().filter(|order| order.refund.is_none()) {
+        let position = groups
+            .iter()
+            .position(|(typ, zone, _, _)| *typ == order.typ && *zone == order.zone)
+            .unwrap_or_else(|| {
+                groups.push((order.typ.clone(), order.zone.clone(), 0, 0));
+                groups.len() - 1
+            });
+        let checked_in = order.checked_in_at.is_some() as u32;
+        groups[position].2 += checked_in;
+        groups[position].3 += 1;
+        total_checked_in += checked_in;
+        total_valid += 1;
+    }
+    groups.sort();
+
+    let mut attendance = format!("TOTAL checked_in={} tickets={}\n", total_checked_in, total_valid);
+    for (typ, zone, checked_in, valid) in groups {
+        attendance.push_str(&format!("{} {} checked_in={} tickets={}\n", typ, zone, checked_in, valid));
+    }
+    attendance.push_str(END_MARKER);
+    attendance.push('\n');
+    attendance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn availability_counts_seats_by_status() {
+        let seating_structure = SeatingStructure::new();
+        let vip = availability(&seating_structure, Some("VIP"), Some("ZonaA"));
+        assert_eq!((vip.total, vip.free, vip.reserved, vip.purchased, vip.blocked), (5, 2, 1, 2, 0));
+        assert_eq!(vip.sell_through(), 40.0);
+        assert_eq!(availability(&seating_structure, None, None).total, 30);
+    }
+
+    #[test]
+    fn format_stats_filters_by_type_zone_or_both() {
+        let seating_structure = SeatingStructure::new();
+        let both = format_stats(&seating_structure, &["VIP", "ZonaA"]).unwrap();
+        assert_eq!(both, "TOTAL total=5 free=2 reserved=1 purchased=2 blocked=0 sold=40.0% avg_vision=100.0\nEND\n");
+
+        // Solo la zona: una línea por tipo dentro de ella
+        let zone = format_stats(&seating_structure, &["ZonaB"]).unwrap();
+        assert!(zone.starts_with("TOTAL total=10 free=10 "));
+        assert!(zone.contains("\nVIP total=5 "));
+        assert!(zone.contains("\nGeneral ZonaB total=5 "));
+        assert!(!zone.contains("ZonaA"));
+
+        let typ = format_stats(&seating_structure, &["General"]).unwrap();
+        assert!(typ.starts_with("TOTAL total=15 "));
+        assert!(typ.contains("\nZonaC total=5 "));
+    }
+
+    #[test]
+    fn format_stats_rejects_unknown_filters() {
+        let seating_structure = SeatingStructure::new();
+        assert_eq!(
+            format_stats(&seating_structure, &["Palco", "ZonaA"]),
+            Err(TicketingError::InvalidCategory("Palco".to_string()))
+        );
+        assert_eq!(
+            format_stats(&seating_structure, &["ZonaZ"]),
+            Err(TicketingError::InvalidArgument("Invalid zone: ZonaZ".to_string()))
+        );
+        assert!(matches!(
+            format_stats(&seating_structure, &["VIP", "ZonaA", "extra"]),
+            Err(TicketingError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,308 @@
+use super::access::SeatFeatures;
+use super::logging;
+use super::orders::{Order, OrderBook, Refund};
+use super::notify::Notifier;
+use super::waitlist::Waitlist;
+use super::{Category, General, Seat, SeatStatus, SeatingStructure, Vip, Zone};
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum StorageStatus {
+    /**
+    Resultado de cargar el estado al iniciar el servidor:
+    - Defaults: No había archivo, se usa la distribución inicial.
+    - Loaded: Se cargó el archivo indicado.
+    - Failed: El archivo existe pero no se pudo leer. Se usa el siguiente archivo de la lista
+      o la distribución inicial, las compras quedan detenidas y el archivo no se sobrescribe.
+    */
+    #[default]
+    Defaults,
+    Loaded(String),
+    Failed { path: String, error: String },
+}
+
+impl StorageStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageStatus::Defaults => "defaults",
+            StorageStatus::Loaded(_) => "loaded",
+            StorageStatus::Failed { .. } => "failed",
+        }
+    }
+}
+
+pub fn format_state(seating_structure: &SeatingStructure) -> String {
+    /**
+    Convierte el estado de los asientos a texto, una línea por asiento:
+    `categoria zona tipo numero vision precio estado atributos`, con los atributos
+    de accesibilidad separados por comas o `-` si el asiento no tiene.
+
+    Los tipos con la venta cerrada se escriben como `closed tipo` y las compras como
+    `order id boleto tipo zona numero precio comprado reembolso reembolsado escaneado`, con `-` si no hubo
+    reembolso o no se ha escaneado.
+    */
+    let mut contents = String::from("# category zone type number vision price status features\n");
+    for typ in &seating_structure.closed_sales {
+        contents.push_str(&format!("closed {}\n", typ));
+    }
+    for (index, category) in seating_structure.categories.iter().enumerate() {
+        for zone in &category.zones {
+            for (typ, seats) in [("VIP", &zone.Vip.seats), ("General", &zone.General.seats)] {
+                for seat in seats {
+                    contents.push_str(&format!(
+                        "{} {} {} {} {} {} {:?} {}\n",
+                        index,
+                        zone.name,
+                        typ,
+                        seat.number,
+                        seat.vision_percentage,
+                        seat.price,
+                        seat.status,
+                        seat.features.format()
+                    ));
+                }
+            }
+        }
+    }
+    for order in &seating_structure.orders.orders {
+        let (amount, refunded_at) = match &order.refund {
+            Some(refund) => (refund.amount.to_string(), refund.refunded_at.to_string()),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let checked_in_at = order.checked_in_at.map_or("-".to_string(), |at| at.to_string());
+        contents.push_str(&format!(
+            "order {} {} {} {} {} {} {} {} {} {}\n",
+            order.id,
+            order.ticket_id,
+            order.typ,
+            order.zone,
+            order.number,
+            order.price,
+            order.purchased_at,
+            amount,
+            refunded_at,
+            checked_in_at
+        ));
+    }
+    contents
+}
+
+pub fn save_state(seating_structure: &SeatingStructure, path: &str) -> io::Result<()> {
+    /**
+    Guarda el estado de los asientos en un archivo con el formato de `format_state`.
+
+    Se escribe primero a un archivo temporal y luego se renombra,
+    así un cierre a medias nunca deja el archivo corrupto.
+    */
+    let temp_path = format!("{}.tmp", path);
+    fs::write(&temp_path, format_state(seating_structure))?;
+    fs::rename(&temp_path, path)
+}
+
+pub fn load_state(path: &str) -> io::Result<SeatingStructure> {
+    /**
+    Carga el estado guardado por `save_state`.
+    Las reservas no se guardan, los asientos vuelven como libres o comprados.
+    Las órdenes se cargan sin conexión dueña.
+    */
+    let contents = fs::read_to_string(path)?;
+    let mut categories: Vec<Category> = Vec::new();
+    let mut closed_sales: Vec<String> = Vec::new();
+    let mut orders = OrderBook::default();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path, line_number + 1, reason),
+            )
+        };
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if let ["closed", typ] = parts.as_slice() {
+            closed_sales.push(typ.to_string());
+            continue;
+        }
+        if let ["order", id, ticket_id, typ, zone, number, price, purchased_at, amount, refunded_at, checked_in_at] =
+            parts.as_slice()
+        {
+            let refund = match (*amount, *refunded_at) {
+                ("-", "-") => None,
+                (amount, refunded_at) => Some(Refund {
+                    amount: amount.parse().map_err(|_| invalid("invalid refund amount"))?,
+                    refunded_at: refunded_at.parse().map_err(|_| invalid("invalid refund time"))?,
+                }),
+            };
+            orders.orders.push(Order {
+                id: id.parse().map_err(|_| invalid("invalid order id"))?,
+                ticket_id: ticket_id.to_string(),
+                holder: None,
+                typ: typ.to_string(),
+                zone: zone.to_string(),
+                number: number.parse().map_err(|_| invalid("invalid seat number"))?,
+                price: price.parse().map_err(|_| invalid("invalid price"))?,
+                purchased_at: purchased_at.parse().map_err(|_| invalid("invalid purchase time"))?,
+                refund,
+                checked_in_at: match *checked_in_at {
+                    "-" => None,
+                    at => Some(at.parse().map_err(|_| invalid("invalid check-in time"))?),
+                },
+            });
+            continue;
+        }
+        // Los archivos anteriores a los atributos de accesibilidad tienen 7 campos
+        let (index, zone_name, typ, number, vision, price, status, features) = match parts.as_slice() {
+            [index, zone_name, typ, number, vision, price, status] => {
+                (index, zone_name, typ, number, vision, price, status, SeatFeatures::default())
+            }
+            [index, zone_name, typ, number, vision, price, status, features] => {
+                let features = SeatFeatures::parse(features).map_err(|_| invalid("invalid seat features"))?;
+                (index, zone_name, typ, number, vision, price, status, features)
+            }
+            _ => return Err(invalid("expected 7 or 8 fields")),
+        };
+        let index: usize = index.parse().map_err(|_| invalid("invalid category index"))?;
+        let seat = Seat {
+            status: match *status {
+                "Free" | "Reserved" => SeatStatus::Free,
+                "Purchased" => SeatStatus::Purchased,
+                "Blocked" => SeatStatus::Blocked,
+                _ => return Err(invalid("invalid seat status")),
+            },
+            vision_percentage: vision.parse().map_err(|_| invalid("invalid vision percentage"))?,
+            number: number.parse().map_err(|_| invalid("invalid seat number"))?,
+            price: price.parse().map_err(|_| invalid("invalid price"))?,
+            holder: None,
+            features,
+        };
+
+        while categories.len() <= index {
+            categories.push(Category { zones: vec![] });
+        }
+        let zones = &mut categories[index].zones;
+        let zone = match zones.iter().position(|zone| zone.name == *zone_name) {
+            Some(position) => &mut zones[position],
+            None => {
+                zones.push(Zone {
+                    name: zone_name.to_string(),
+                    Vip: Vip { seats: vec![] },
+                    General: General { seats: vec![] },
+                });
+                zones.last_mut().unwrap()
+            }
+        };
+        match *typ {
+            "VIP" => zone.Vip.seats.push(seat),
+            "General" => zone.General.seats.push(seat),
+            _ => return Err(invalid("invalid seat type")),
+        }
+    }
+
+    Ok(SeatingStructure {
+        categories,
+        closed_sales,
+        orders,
+        waitlist: Waitlist::default(),
+        notifier: Notifier::default(),
+        storage: StorageStatus::default(),
+        started_at: Instant::now(),
+    })
+}
+
+pub fn load_or_default(paths: &[Option<&str>]) -> SeatingStructure {
+    /**
+    Carga el primer archivo que exista de la lista.
+    Si uno existe pero no se puede leer, se sigue con el siguiente (el estado guardado
+    cae a la distribución del lugar) y si ninguno sirve se usa la estructura inicial.
+    El resultado queda en `storage`: con una falla no se vende ni se guarda encima del archivo.
+    */
+    let mut failure: Option<StorageStatus> = None;
+    for path in paths.iter().flatten().filter(|path| Path::new(path).exists()) {
+        match load_state(path) {
+            Ok(mut seating_structure) => {
+                logging::info("Loaded seating state", json!({ "path": path }));
+                seating_structure.storage = failure.unwrap_or_else(|| StorageStatus::Loaded(path.to_string()));
+                return seating_structure;
+            }
+            Err(e) => {
+                logging::error("Failed to load seating state, sales are paused", json!({ "path": path, "error": e.to_string() }));
+                // Se recuerda la primera falla, es la del archivo que no se puede perder
+                failure.get_or_insert(StorageStatus::Failed { path: path.to_string(), error: e.to_string() });
+            }
+        }
+    }
+    let mut seating_structure = SeatingStructure::new();
+    seating_structure.storage = failure.unwrap_or_default();
+    seating_structure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::error::TicketingError;
+    use std::env;
+
+    fn temp_path(name: &str) -> String {
+        // Archivo propio de cada prueba, las pruebas corren en paralelo
+        env::temp_dir()
+            .join(format!("event_ticketing_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp_path("round_trip");
+        let mut seating_structure = SeatingStructure::new();
+        seating_structure.purchase_seat("VIP", "ZonaB", 2, 7).unwrap();
+        seating_structure.closed_sales.push("General".to_string());
+        save_state(&seating_structure, &path).unwrap();
+
+        let loaded = load_state(&path).unwrap();
+        save_state(&loaded, &path).unwrap();
+        let reloaded = load_state(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(format_state(&reloaded), format_state(&loaded));
+        assert_eq!(loaded.closed_sales, vec!["General".to_string()]);
+        assert_eq!(loaded.seat("VIP", "ZonaB", 2).map(|seat| &seat.status), Some(&SeatStatus::Purchased));
+        // Las reservas no se guardan, el asiento vuelve libre
+        assert_eq!(loaded.seat("VIP", "ZonaA", 5).map(|seat| &seat.status), Some(&SeatStatus::Free));
+        assert_eq!(loaded.orders.orders.len(), 1);
+        assert_eq!(loaded.orders.orders[0].holder, None);
+    }
+
+    #[test]
+    fn load_or_default_falls_back_and_pauses_sales_when_the_state_is_broken() {
+        let state = temp_path("broken_state");
+        let venue = temp_path("venue");
+        fs::write(&state, "0 ZonaA VIP not-a-number 100 150 Free -\n").unwrap();
+        save_state(&SeatingStructure::new(), &venue).unwrap();
+
+        let mut seating_structure = load_or_default(&[Some(&state), Some(&venue)]);
+        fs::remove_file(&venue).unwrap();
+        // Los asientos vienen del lugar, pero la falla del estado guardado sigue registrada
+        assert_eq!(seating_structure.storage.name(), "failed");
+        assert!(seating_structure.seat("VIP", "ZonaB", 1).is_some());
+        assert_eq!(
+            seating_structure.purchase_seat("VIP", "ZonaB", 1, 7),
+            Err(TicketingError::StorageUnavailable(state.clone()))
+        );
+        // El archivo roto queda como estaba
+        assert!(fs::read_to_string(&state).unwrap().contains("not-a-number"));
+        fs::remove_file(&state).unwrap();
+    }
+
+    #[test]
+    fn load_or_default_uses_defaults_without_files() {
+        let seating_structure = load_or_default(&[Some(&temp_path("missing")), None]);
+        assert_eq!(seating_structure.storage, StorageStatus::Defaults);
+    }
+}
@@ -0,0 +1,147 @@
+use super::orders::Order;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TicketClaims {
+    /**
+    Datos de un boleto que van firmados dentro del código:
+    - `ticket_id`: Identificador único del boleto.
+    - `event`: Nombre del evento.
+    - `typ`, `zone`, `number`: Asiento del boleto.
+    - `price`: Precio pagado.
+    */
+    pub ticket_id: String,
+    pub event: String,
+    pub typ: String,
+    pub zone: String,
+    pub number: u32,
+    pub price: u32,
+}
+
+impl TicketClaims {
+    pub fn from_order(order: &Order, event: &str) -> Self {
+        TicketClaims {
+            ticket_id: order.ticket_id.clone(),
+            event: event.to_string(),
+            typ: order.typ.clone(),
+            zone: order.zone.clone(),
+            number: order.number,
+            price: order.price,
+        }
+    }
+
+    fn payload(&self) -> String {
+        // Los campos separados por punto, ninguno puede contener un punto
+        format!(
+            "{}.{}.{}.{}.{}.{}",
+            self.ticket_id, self.event, self.typ, self.zone, self.number, self.price
+        )
+    }
+}
+
+pub fn new_ticket_id(order_id: u64) -> String {
+    // Número de orden más una parte aleatoria, así el id no se puede adivinar
+    format!("T{}-{:08X}", order_id, rand::thread_rng().gen::<u32>())
+}
+
+pub fn new_secret() -> Vec<u8> {
+    // Clave aleatoria para cuando no se configura una
+    let mut rng = rand::thread_rng();
+    (0..32).map(|_| rng.gen()).collect()
+}
+
+fn signature(secret: &[u8], payload: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn issue_code(secret: &[u8], claims: &TicketClaims) -> String {
+    /**
+    Genera el código del boleto: los datos del boleto seguidos de su firma HMAC-SHA256.
+    Formato: `id.evento.tipo.zona.numero.precio.firma`
+
+    Con la clave se puede verificar el código sin consultar al servidor.
+    */
+    let payload = claims.payload();
+    let signature = signature(secret, &payload);
+    format!("{}.{}", payload, signature)
+}
+
+pub fn verify_code(secret: &[u8], code: &str) -> Result<TicketClaims, String> {
+    /**
+    Verifica la firma de un código de boleto y retorna sus datos.
+    Retorna un error si el código está mal formado o la firma no coincide.
+    */
+    let (payload, signature) = code.rsplit_once('.').ok_or("Malformed ticket code.")?;
+    let signature = (0..signature.len())
+        .step_by(2)
+        .map(|index| signature.get(index..index + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or("Malformed ticket code.")?;
+
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    // Comparación en tiempo constante
+    mac.verify_slice(&signature).map_err(|_| "Invalid ticket signature.".to_string())?;
+
+    let parts: Vec<&str> = payload.split('.').collect();
+    let [ticket_id, event, typ, zone, number, price] = parts.as_slice() else {
+        return Err("Malformed ticket code.".to_string());
+    };
+    Ok(TicketClaims {
+        ticket_id: ticket_id.to_string(),
+        event: event.to_string(),
+        typ: typ.to_string(),
+        zone: zone.to_string(),
+        number: number.parse().map_err(|_| "Malformed ticket code.")?,
+        price: price.parse().map_err(|_| "Malformed ticket code.")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims() -> TicketClaims {
+        TicketClaims {
+            ticket_id: "T1-0000ABCD".to_string(),
+            event: "concierto".to_string(),
+            typ: "VIP".to_string(),
+            zone: "ZonaA".to_string(),
+            number: 2,
+            price: 150,
+        }
+    }
+
+    #[test]
+    fn verify_code_returns_the_signed_claims() {
+        let code = issue_code(b"secreto", &claims());
+        assert_eq!(verify_code(b"secreto", &code), Ok(claims()));
+    }
+
+    #[test]
+    fn verify_code_rejects_other_secrets_and_tampering() {
+        let code = issue_code(b"secreto", &claims());
+        assert_eq!(verify_code(b"otra", &code), Err("Invalid ticket signature.".to_string()));
+        let tampered = code.replacen("ZonaA", "ZonaB", 1);
+        assert_eq!(verify_code(b"secreto", &tampered), Err("Invalid ticket signature.".to_string()));
+    }
+
+    #[test]
+    fn verify_code_rejects_malformed_codes() {
+        let code = issue_code(b"secreto", &claims());
+        let (payload, _) = code.rsplit_once('.').unwrap();
+        for malformed in ["", "sin-firma", &format!("{}.zz", payload), &format!("{}.abc", payload)] {
+            assert_eq!(verify_code(b"secreto", malformed), Err("Malformed ticket code.".to_string()), "{}", malformed);
+        }
+    }
+}
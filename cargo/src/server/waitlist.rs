@@ -0,0 +1,359 @@
+use super::error::TicketingError;
+use super::logging;
+use super::metrics;
+use super::search::FindConstraints;
+use super::{SeatStatus, SeatingStructure, MAX_SEATS_PER_REQUEST};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub struct WaitlistEntry {
+    /**
+    Cliente esperando asientos de un tipo agotado:
+    - `holder`: Conexión del cliente.
+    - `typ`: Tipo de asiento ("VIP" o "General").
+    - `seat_count`: Cantidad de asientos que necesita el grupo.
+    */
+    pub holder: u64,
+    pub typ: String,
+    pub seat_count: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Offer {
+    /**
+    Asientos apartados para un cliente de la lista de espera:
+    - `holder`: Conexión a la que se le ofrecen.
+    - `typ`: Tipo de asiento.
+    - `seats`: Asientos apartados (zona, número), quedan reservados a nombre del cliente.
+    - `expires_at`: Momento en que vence la oferta si no se reclama.
+    */
+    pub holder: u64,
+    pub typ: String,
+    pub seats: Vec<(String, u32)>,
+    pub expires_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct Waitlist {
+    /**
+    Lista de espera del evento:
+    - `entries`: Clientes esperando, en orden de llegada.
+    - `offers`: Ofertas pendientes de reclamar.
+    - `claim_window`: Tiempo que tiene un cliente para reclamar su oferta.
+    */
+    pub entries: VecDeque<WaitlistEntry>,
+    pub offers: Vec<Offer>,
+    pub claim_window: Duration,
+}
+
+impl Default for Waitlist {
+    fn default() -> Self {
+        Waitlist { entries: VecDeque::new(), offers: vec![], claim_window: Duration::from_secs(60) }
+    }
+}
+
+impl Waitlist {
+    pub fn position(&self, holder: u64) -> Option<usize> {
+        // Posición en la fila entre los que esperan el mismo tipo, empezando en 1
+        let entry = self.entries.iter().find(|entry| entry.holder == holder)?;
+        let ahead = self
+            .entries
+            .iter()
+            .take_while(|other| other.holder != holder)
+            .filter(|other| other.typ == entry.typ)
+            .count();
+        Some(ahead + 1)
+    }
+
+    pub fn offer(&self, holder: u64) -> Option<&Offer> {
+        self.offers.iter().find(|offer| offer.holder == holder)
+    }
+
+    pub fn leave(&mut self, holder: u64) -> bool {
+        /**
+        Saca a una conexión de la fila y descarta su oferta.
+        Los asientos de la oferta siguen reservados a su nombre, quien llama debe liberarlos.
+        */
+        let waiting = self.entries.len() + self.offers.len();
+        self.entries.retain(|entry| entry.holder != holder);
+        self.offers.retain(|offer| offer.holder != holder);
+        waiting != self.entries.len() + self.offers.len()
+    }
+}
+
+impl SeatingStructure {
+    pub fn join_waitlist(&mut self, holder: u64, typ: &str, seat_count: u32) -> Result<(), TicketingError> {
+        /**
+        Anota a una conexión en la lista de espera de un tipo de asiento.
+        Una conexión solo puede estar una vez en la lista.
+        */
+        if typ != "VIP" && typ != "General" {
+            return Err(TicketingError::InvalidCategory(typ.to_string()));
+        }
+        if seat_count == 0 || seat_count as usize > MAX_SEATS_PER_REQUEST {
+            return Err(TicketingError::InvalidSeatCount);
+        }
+        if self.waitlist.position(holder).is_some() || self.waitlist.offer(holder).is_some() {
+            return Err(TicketingError::AlreadyOnWaitlist);
+        }
+        self.waitlist.entries.push_back(WaitlistEntry { holder, typ: typ.to_string(), seat_count });
+        self.offer_freed_seats();
+        Ok(())
+    }
+
+    pub fn leave_waitlist(&mut self, holder: u64) -> bool {
+        // Sale de la fila y devuelve los asientos de su oferta, si tenía una
+        if !self.waitlist.leave(holder) {
+            return false;
+        }
+        self.release_holds(holder);
+        true
+    }
+
+    pub fn offer_freed_seats(&mut self) -> Vec<Offer> {
+        /**
+        Ofrece los asientos libres a la lista de espera en orden de llegada.
+        Cada tipo de asiento es una fila aparte: si el primer grupo de un tipo todavía
+        no cabe, los que vienen detrás de ese tipo esperan a que se le ofrezca a él.
+
+        Los asientos ofrecidos quedan reservados a nombre del cliente hasta que
+        los reclame o venza la oferta. Retorna las ofertas nuevas.
+        */
+        let mut offers = Vec::new();
+        // Tipos cuya fila quedó detenida en un grupo que no cabe o con la venta cerrada
+        let mut stopped: Vec<String> = Vec::new();
+        let mut index = 0;
+        while index < self.waitlist.entries.len() {
+            let entry = &self.waitlist.entries[index];
+            if stopped.contains(&entry.typ) {
+                index += 1;
+                continue;
+            }
+            // Los mismos asientos que daría `find`
+            let seats = match self.find_seats(&entry.typ, entry.seat_count, &FindConstraints::default()) {
+                Ok(seats) if !seats.is_empty() && self.sales_open(&entry.typ) => seats,
+                _ => {
+                    stopped.push(entry.typ.clone());
+                    index += 1;
+                    continue;
+                }
+            };
+            let entry = self.waitlist.entries.remove(index).expect("index is in bounds");
+            // El tipo ya se validó al entrar a la fila
+            let _ = self.set_seats_status(&entry.typ, &seats, SeatStatus::Reserved, Some(entry.holder));
+            let offer = Offer {
+                holder: entry.holder,
+                typ: entry.typ,
+                seats,
+                expires_at: Instant::now() + self.waitlist.claim_window,
+            };
+            logging::info(
+                "Waitlist offer",
+                json!({ "conn": offer.holder, "type": offer.typ, "seats": offer.seats.len() }),
+            );
+            self.notifier.offer(&offer, self.waitlist.claim_window.as_secs());
+            self.waitlist.offers.push(offer.clone());
+            offers.push(offer);
+        }
+        offers
+    }
+
+    pub fn expire_offers(&mut self, now: Instant) -> usize {
+        /**
+        Vence las ofertas que no se reclamaron a tiempo y pasa sus asientos
+        al siguiente en la fila. Retorna la cantidad de ofertas vencidas.
+        */
+        let (expired, pending): (Vec<Offer>, Vec<Offer>) =
+            self.waitlist.offers.drain(..).partition(|offer| offer.expires_at <= now);
+        self.waitlist.offers = pending;
+        for offer in &expired {
+            for (zone, number) in &offer.seats {
+                if let Some(seat) = self.seat_mut(&offer.typ, zone, *number) {
+                    if seat.status == SeatStatus::Reserved && seat.holder == Some(offer.holder) {
+                        seat.status = SeatStatus::Free;
+                        seat.holder = None;
+                        self.notifier.hold_expired(&offer.typ, zone, *number);
+                        metrics::holds_expired(1);
+                    }
+                }
+            }
+            logging::info("Waitlist offer expired", json!({ "conn": offer.holder, "type": offer.typ }));
+        }
+        if !expired.is_empty() {
+            self.offer_freed_seats();
+        }
+        expired.len()
+    }
+
+    pub fn withdraw_stale_offers(&mut self) -> usize {
+        /**
+        Retira las ofertas con algún asiento que ya no está reservado ni comprado por su cliente,
+        por ejemplo porque un operador lo bloqueó o lo liberó. Los asientos que seguían reservados
+        se liberan y el cliente vuelve al frente de la fila para recibir una oferta nueva.
+
+        Quien llama debe llamar a `offer_freed_seats` si se retiró alguna.
+        Retorna la cantidad de ofertas retiradas.
+        */
+        let offers = std::mem::take(&mut self.waitlist.offers);
+        let (stale, valid): (Vec<Offer>, Vec<Offer>) = offers.into_iter().partition(|offer| {
+            offer.seats.iter().any(|(zone, number)| {
+                !self.seat(&offer.typ, zone, *number).is_some_and(|seat| {
+                    matches!(seat.status, SeatStatus::Reserved | SeatStatus::Purchased) && seat.holder == Some(offer.holder)
+                })
+            })
+        });
+        self.waitlist.offers = valid;
+        for offer in stale.iter().rev() {
+            for (zone, number) in &offer.seats {
+                if let Some(seat) = self.seat_mut(&offer.typ, zone, *number) {
+                    if seat.status == SeatStatus::Reserved && seat.holder == Some(offer.holder) {
+                        seat.status = SeatStatus::Free;
+                        seat.holder = None;
+                    }
+                }
+            }
+            self.waitlist.entries.push_front(WaitlistEntry {
+                holder: offer.holder,
+                typ: offer.typ.clone(),
+                seat_count: offer.seats.len() as u32,
+            });
+            logging::info("Waitlist offer withdrawn", json!({ "conn": offer.holder, "type": offer.typ }));
+        }
+        stale.len()
+    }
+
+    pub fn claim_offer(&mut self, holder: u64) -> Result<Vec<u64>, TicketingError> {
+        /**
+        Compra los asientos de la oferta de una conexión.
+        Una oferta con asientos que cambiaron se retira antes, el cliente recibe una nueva si hay lugar.
+        Retorna los números de orden, uno por asiento.
+        */
+        if self.withdraw_stale_offers() > 0 {
+            self.offer_freed_seats();
+        }
+        let position = self
+            .waitlist
+            .offers
+            .iter()
+            .position(|offer| offer.holder == holder)
+            .ok_or(TicketingError::NoOffer)?;
+        if !self.sales_open(&self.waitlist.offers[position].typ) {
+            return Err(TicketingError::SalesClosed(self.waitlist.offers[position].typ.clone()));
+        }
+        let offer = self.waitlist.offers.remove(position);
+        let mut order_ids = Vec::new();
+        for (zone, number) in &offer.seats {
+            // Los que ya compró con `purchase` no se vuelven a cobrar
+            let held = self
+                .seat_mut(&offer.typ, zone, *number)
+                .is_some_and(|seat| seat.status == SeatStatus::Reserved && seat.holder == Some(holder));
+            if held {
+                order_ids.push(self.purchase_seat(&offer.typ, zone, *number, holder)?);
+            }
+        }
+        Ok(order_ids)
+    }
+}
+
+pub fn format_status(seating_structure: &SeatingStructure, holder: u64, now: Instant) -> String {
+    // Respuesta del comando `waitlist`: la oferta pendiente o la posición en la fila
+    let waitlist = &seating_structure.waitlist;
+    if let Some(offer) = waitlist.offer(holder) {
+        let seats = offer
+            .seats
+            .iter()
+            .map(|(zone, number)| format!("({},{})", zone, number))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let remaining = offer.expires_at.saturating_duration_since(now).as_secs();
+        return format!("Offer {} {} claim within {}s", offer.typ, seats, remaining);
+    }
+    match (waitlist.position(holder), waitlist.entries.iter().find(|entry| entry.holder == holder)) {
+        (Some(position), Some(entry)) => format!(
+            "Waiting for {} {} seats, position {}",
+            entry.seat_count, entry.typ, position
+        ),
+        _ => "You are not on the waitlist.".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sold_out(typ: &str) -> SeatingStructure {
+        // Estructura sin asientos libres ni reservados del tipo indicado
+        let mut seating_structure = SeatingStructure::new();
+        for zone in ["ZonaA", "ZonaB", "ZonaC"] {
+            for number in 1..=5 {
+                let seat = seating_structure.seat_mut(typ, zone, number).unwrap();
+                seat.status = SeatStatus::Purchased;
+                seat.holder = Some(99);
+            }
+        }
+        seating_structure
+    }
+
+    #[test]
+    fn waitlist_offers_seats_in_order_of_arrival() {
+        let mut seating_structure = sold_out("VIP");
+        seating_structure.join_waitlist(1, "VIP", 2).unwrap();
+        seating_structure.join_waitlist(2, "VIP", 1).unwrap();
+        assert_eq!((seating_structure.waitlist.position(1), seating_structure.waitlist.position(2)), (Some(1), Some(2)));
+
+        // Un asiento no alcanza para el primero, el segundo no se le adelanta
+        seating_structure.operator_release("VIP", "ZonaB", 1).unwrap();
+        assert!(seating_structure.waitlist.offers.is_empty());
+        assert_eq!(seating_structure.waitlist.position(2), Some(2));
+
+        seating_structure.operator_release("VIP", "ZonaB", 2).unwrap();
+        let offer = seating_structure.waitlist.offer(1).unwrap();
+        assert_eq!(offer.seats, vec![("ZonaB".to_string(), 1), ("ZonaB".to_string(), 2)]);
+        assert_eq!(seating_structure.seat("VIP", "ZonaB", 1).and_then(|seat| seat.holder), Some(1));
+        assert_eq!(seating_structure.waitlist.position(2), Some(1));
+    }
+
+    #[test]
+    fn waitlist_rows_of_each_type_are_independent() {
+        let mut seating_structure = sold_out("VIP");
+        seating_structure.join_waitlist(1, "VIP", 1).unwrap();
+        // General tiene lugar, no espera al grupo VIP
+        seating_structure.join_waitlist(2, "General", 1).unwrap();
+        assert!(seating_structure.waitlist.offer(2).is_some());
+        assert_eq!(seating_structure.waitlist.position(1), Some(1));
+        assert_eq!(seating_structure.join_waitlist(1, "General", 1), Err(TicketingError::AlreadyOnWaitlist));
+    }
+
+    #[test]
+    fn claim_offer_buys_the_offered_seats_once() {
+        let mut seating_structure = sold_out("VIP");
+        assert_eq!(seating_structure.claim_offer(1), Err(TicketingError::NoOffer));
+        seating_structure.join_waitlist(1, "VIP", 1).unwrap();
+        seating_structure.operator_release("VIP", "ZonaC", 3).unwrap();
+
+        let order_ids = seating_structure.claim_offer(1).unwrap();
+        assert_eq!(order_ids.len(), 1);
+        assert_eq!(seating_structure.seat("VIP", "ZonaC", 3).map(|seat| seat.status.clone()), Some(SeatStatus::Purchased));
+        assert_eq!(seating_structure.claim_offer(1), Err(TicketingError::NoOffer));
+    }
+
+    #[test]
+    fn blocking_an_offered_seat_withdraws_the_offer() {
+        let mut seating_structure = sold_out("VIP");
+        seating_structure.join_waitlist(1, "VIP", 2).unwrap();
+        seating_structure.operator_release("VIP", "ZonaA", 2).unwrap();
+        seating_structure.operator_release("VIP", "ZonaA", 3).unwrap();
+        assert!(seating_structure.waitlist.offer(1).is_some());
+
+        let seat = seating_structure.seat_mut("VIP", "ZonaA", 2).unwrap();
+        seat.status = SeatStatus::Blocked;
+        seat.holder = None;
+        assert_eq!(seating_structure.withdraw_stale_offers(), 1);
+        // El otro asiento vuelve a estar libre y el cliente sigue primero en la fila
+        assert_eq!(seating_structure.seat("VIP", "ZonaA", 3).map(|seat| seat.status.clone()), Some(SeatStatus::Free));
+        assert_eq!(seating_structure.waitlist.position(1), Some(1));
+        assert_eq!(seating_structure.claim_offer(1), Err(TicketingError::NoOffer));
+    }
+}
@@ -0,0 +1,346 @@
+use super::error::TicketingError;
+use super::http::{seat_json, seat_number};
+use super::logging;
+use super::metrics;
+use super::notify::{Event, Filter};
+use super::pool::ConnectionSlot;
+use super::{read_seats, write_seats, ConnectionHolds, SeatingStructure, SHUTDOWN_POLL_INTERVAL};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::{Error, Message};
+
+// Navegadores conectados a la vez, cada uno ocupa un hilo mientras está abierto
+const MAX_CONNECTIONS: usize = 64;
+
+// Tiempo para completar el handshake, un cliente que no lo envía no retiene su hilo
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Acciones que acepta `handle_message`
+const ACTIONS: [&str; 3] = ["snapshot", "hold", "release"];
+
+pub fn start_websocket(
+    address: String,
+    seating_structure: Arc<RwLock<SeatingStructure>>,
+    next_holder: Arc<AtomicU64>,
+    shutdown: Arc<AtomicBool>,
+) -> io::Result<()> {
+    /**
+    Inicia el WebSocket del mapa en vivo en su propio hilo, un hilo por navegador.
+
+    Al conectarse el navegador recibe el estado de todos los asientos
+    (`{"kind": "snapshot", ...}`) y después cada cambio a medida que ocurre.
+    Puede enviar `{"action": "hold"|"release", "type": "VIP", "zone": "ZonaA", "number": 2}`
+    y `{"action": "snapshot"}` para pedir el estado completo de nuevo.
+    Con `MAX_CONNECTIONS` navegadores conectados, el siguiente recibe 503 sin handshake.
+    */
+    let listener = TcpListener::bind(&address)?;
+    listener.set_nonblocking(true)?;
+    logging::info("WebSocket is listening", json!({ "address": address }));
+
+    let active_connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        while !shutdown.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((mut stream, _)) => {
+                    let slot = match ConnectionSlot::acquire(&active_connections, MAX_CONNECTIONS) {
+                        Some(slot) => slot,
+                        None => {
+                            logging::warn("WebSocket busy, connection rejected", json!({ "max_connections": MAX_CONNECTIONS }));
+                            if let Err(e) = reject_busy(&mut stream) {
+                                logging::warn("Failed to reject WebSocket connection", json!({ "error": e.to_string() }));
+                            }
+                            continue;
+                        }
+                    };
+                    let id = next_holder.fetch_add(1, Ordering::SeqCst);
+                    let seating_structure = Arc::clone(&seating_structure);
+                    let shutdown = Arc::clone(&shutdown);
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        handle_websocket(stream, id, seating_structure, shutdown)
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(SHUTDOWN_POLL_INTERVAL),
+                Err(e) => logging::warn("Failed to accept WebSocket connection", json!({ "error": e.to_string() })),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn reject_busy(stream: &mut TcpStream) -> io::Result<()> {
+    // Se rechaza antes del handshake, el navegador ve un 503 con el código del error
+    stream.set_nonblocking(false)?;
+    let body = error_json(&TicketingError::ServerBusy).to_string();
+    write!(
+        stream,
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+fn snapshot(seating_structure: &SeatingStructure) -> Value {
+    // Estado completo de los asientos del evento
+    let mut seats = Vec::new();
+    for category in &seating_structure.categories {
+        for zone in &category.zones {
+            for (typ, zone_seats) in [("VIP", &zone.Vip.seats), ("General", &zone.General.seats)] {
+                seats.extend(zone_seats.iter().map(|seat| seat_json(typ, &zone.name, seat)));
+            }
+        }
+    }
+    json!({
+        "kind": "snapshot",
+        "event": seating_structure.orders.event,
+        "sales_open": {
+            "VIP": seating_structure.sales_open("VIP"),
+            "General": seating_structure.sales_open("General"),
+        },
+        "seats": seats,
+    })
+}
+
+fn event_json(event: &Event) -> Value {
+    match event {
+        Event::Seat { typ, zone, number, status } => json!({
+            "kind": "seat",
+            "type": typ,
+            "zone": zone,
+            "number": number,
+            "status": format!("{:?}", status),
+        }),
+        Event::HoldExpired { typ, zone, number } => json!({
+            "kind": "hold_expired",
+            "type": typ,
+            "zone": zone,
+            "number": number,
+        }),
+        Event::Offer { typ, seats, claim_window } => json!({
+            "kind": "offer",
+            "type": typ,
+            "seats": seats.iter().map(|(zone, number)| json!({ "zone": zone, "number": number })).collect::<Vec<Value>>(),
+            "claim_window": claim_window,
+        }),
+    }
+}
+
+fn error_json(error: &TicketingError) -> Value {
+    json!({ "kind": "error", "code": error.code(), "message": error.to_string() })
+}
+
+fn handle_message(text: &str, id: u64, seating_structure: &RwLock<SeatingStructure>) -> Value {
+    /**
+    Atiende un mensaje del navegador y retorna la respuesta.
+    Las reservas quedan a nombre de la conexión y se liberan al desconectarse.
+    */
+    let message: Value = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => return error_json(&TicketingError::MalformedRequest(format!("Invalid JSON: {}", e))),
+    };
+    let action = message["action"].as_str().unwrap_or("");
+    match action {
+        "snapshot" => return snapshot(&read_seats(seating_structure)),
+        "hold" | "release" => {}
+        _ => return error_json(&TicketingError::InvalidCommand(action.to_string())),
+    }
+    let (typ, zone) = match (message["type"].as_str(), message["zone"].as_str(), message["number"].as_u64()) {
+        (Some(typ), Some(zone), Some(_)) => (typ, zone),
+        _ => return error_json(&TicketingError::InvalidArgument("Expected type, zone and number.".to_string())),
+    };
+    let number = match seat_number(&message["number"]) {
+        Ok(number) => number,
+        Err(e) => return error_json(&e),
+    };
+    let mut seating_structure = write_seats(seating_structure);
+    let (kind, result) = match action {
+        "hold" => ("held", seating_structure.hold_seat(typ, zone, number, id)),
+        _ => ("released", seating_structure.release_seat(typ, zone, number, id)),
+    };
+    match result {
+        Ok(()) => json!({ "kind": kind, "type": typ, "zone": zone, "number": number }),
+        Err(e) => {
+            let mut error = error_json(&e);
+            error["action"] = action.into();
+            error
+        }
+    }
+}
+
+fn handle_websocket(stream: TcpStream, id: u64, seating_structure: Arc<RwLock<SeatingStructure>>, shutdown: Arc<AtomicBool>) {
+    /**
+    Atiende un navegador: envía el estado inicial, reenvía los cambios
+    y responde a los mensajes de reserva y liberación.
+    */
+    if let Err(e) = stream.set_nonblocking(false).and_then(|_| stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))) {
+        logging::warn("Failed to configure WebSocket connection", json!({ "conn": id, "error": e.to_string() }));
+        return;
+    }
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(e) => {
+            logging::warn("WebSocket handshake failed", json!({ "conn": id, "error": e.to_string() }));
+            return;
+        }
+    };
+    // Ya conectado, despierta seguido para reenviar eventos y revisar la señal de cierre
+    if let Err(e) = socket.get_ref().set_read_timeout(Some(SHUTDOWN_POLL_INTERVAL)) {
+        logging::warn("Failed to configure WebSocket connection", json!({ "conn": id, "error": e.to_string() }));
+        return;
+    }
+    logging::info("WebSocket connected", json!({ "conn": id }));
+    metrics::connection_opened("ws");
+    let _holds = ConnectionHolds { id, seating_structure: Arc::clone(&seating_structure) };
+
+    // El estado inicial y la suscripción se toman juntos, así no se pierde ningún cambio
+    let (initial, events) = {
+        let mut seating_structure = write_seats(&seating_structure);
+        let events = seating_structure.notifier.subscribe(id, Filter::default());
+        (snapshot(&seating_structure), events)
+    };
+    let mut outgoing = vec![initial];
+
+    loop {
+        if shutdown.load(Ordering::SeqCst) {
+            let _ = socket.close(None);
+            let _ = socket.flush();
+            logging::info("WebSocket closed by server shutdown", json!({ "conn": id }));
+            break;
+        }
+        outgoing.extend(events.try_iter().map(|event| event_json(&event)));
+        if let Err(e) = outgoing.drain(..).try_for_each(|message| socket.send(Message::Text(message.to_string())).map_err(Box::new)) {
+            logging::warn("Failed to write to WebSocket", json!({ "conn": id, "error": e.to_string() }));
+            break;
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let request_id = logging::next_request_id();
+                let started = Instant::now();
+                logging::debug("Received WebSocket message", json!({ "conn": id, "req": request_id, "message": text }));
+                let reply = handle_message(&text, id, &seating_structure);
+                let action = serde_json::from_str::<Value>(&text).ok().and_then(|message| message["action"].as_str().map(String::from));
+                let action = ACTIONS.iter().find(|known| Some(**known) == action.as_deref()).copied();
+                metrics::command("ws", action.unwrap_or("unknown"), reply["code"].as_str().unwrap_or("ok"));
+                logging::info(
+                    "WebSocket request",
+                    json!({
+                        "conn": id,
+                        "req": request_id,
+                        "outcome": reply["code"].as_str().unwrap_or("ok"),
+                        "reply": reply["kind"],
+                        "duration_us": started.elapsed().as_micros() as u64,
+                    }),
+                );
+                outgoing.push(reply);
+            }
+            Ok(Message::Binary(_)) => {
+                outgoing.push(error_json(&TicketingError::MalformedRequest("Binary messages are not supported.".to_string())))
+            }
+            Ok(Message::Close(_)) => {
+                logging::info("WebSocket client disconnected", json!({ "conn": id }));
+                break;
+            }
+            // Los ping y pong los responde tungstenite
+            Ok(_) => {}
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {}
+            Err(Error::ConnectionClosed) | Err(Error::AlreadyClosed) => {
+                logging::info("WebSocket client disconnected", json!({ "conn": id }));
+                break;
+            }
+            Err(e) => {
+                logging::warn("Failed to read from WebSocket", json!({ "conn": id, "error": e.to_string() }));
+                break;
+            }
+        }
+    }
+    metrics::connection_closed("ws");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::WebSocket;
+
+    fn read_json(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Value {
+        // Siguiente mensaje de texto del servidor
+        loop {
+            if let Message::Text(text) = socket.read().expect("Failed to read from WebSocket") {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn handle_message_holds_and_releases_for_the_connection() {
+        let seating_structure = RwLock::new(SeatingStructure::new());
+        let hold = r#"{"action": "hold", "type": "VIP", "zone": "ZonaB", "number": 2}"#;
+        assert_eq!(handle_message(hold, 1, &seating_structure)["kind"], "held");
+        let taken = handle_message(hold, 2, &seating_structure);
+        assert_eq!((taken["kind"].as_str(), taken["code"].as_str(), taken["action"].as_str()), (Some("error"), Some("SEAT_NOT_FREE"), Some("hold")));
+
+        let release = r#"{"action": "release", "type": "VIP", "zone": "ZonaB", "number": 2}"#;
+        assert_eq!(handle_message(release, 2, &seating_structure)["code"], "SEAT_NOT_HELD");
+        assert_eq!(handle_message(release, 1, &seating_structure)["kind"], "released");
+    }
+
+    #[test]
+    fn handle_message_rejects_bad_messages() {
+        let seating_structure = RwLock::new(SeatingStructure::new());
+        assert_eq!(handle_message("{", 1, &seating_structure)["code"], "MALFORMED_REQUEST");
+        assert_eq!(handle_message(r#"{"action": "fly"}"#, 1, &seating_structure)["code"], "INVALID_COMMAND");
+        let no_number = r#"{"action": "hold", "type": "VIP", "zone": "ZonaB"}"#;
+        assert_eq!(handle_message(no_number, 1, &seating_structure)["code"], "INVALID_ARGUMENT");
+        let snapshot = handle_message(r#"{"action": "snapshot"}"#, 1, &seating_structure);
+        assert_eq!(snapshot["seats"].as_array().map(Vec::len), Some(30));
+    }
+
+    #[test]
+    fn websocket_sends_the_snapshot_and_the_changes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let seating_structure = Arc::new(RwLock::new(SeatingStructure::new()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let server = {
+            let (seating_structure, shutdown) = (Arc::clone(&seating_structure), Arc::clone(&shutdown));
+            thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                handle_websocket(stream, 1, seating_structure, shutdown);
+            })
+        };
+
+        let stream = TcpStream::connect(address).unwrap();
+        let (mut socket, _) = tungstenite::client(format!("ws://{}/", address), MaybeTlsStream::Plain(stream)).unwrap();
+        assert_eq!(read_json(&mut socket)["kind"], "snapshot");
+
+        // Un cambio hecho por otra conexión llega como evento
+        write_seats(&seating_structure).hold_seat("General", "ZonaA", 1, 7).unwrap();
+        let event = read_json(&mut socket);
+        assert_eq!((event["kind"].as_str(), event["status"].as_str()), (Some("seat"), Some("Reserved")));
+
+        shutdown.store(true, Ordering::SeqCst);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn handshake_is_not_waited_for_forever() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let started = Instant::now();
+            handle_websocket(stream, 1, Arc::new(RwLock::new(SeatingStructure::new())), Arc::new(AtomicBool::new(false)));
+            started.elapsed()
+        });
+        // Se conecta y nunca envía el handshake
+        let _idle = TcpStream::connect(address).unwrap();
+        let waited = server.join().unwrap();
+        assert!(waited >= HANDSHAKE_TIMEOUT && waited < HANDSHAKE_TIMEOUT * 2);
+    }
+}